@@ -1,27 +1,128 @@
 use env_logger::Env;
+use server::config::config::ServerConfig;
 use server::error::error::Error;
-use server::net::connection::DataStreamConnection;
 use server::net::server::TcpServer;
-use server::user::session::DefaultSessionManager;
-use log::{error, info, warn};
-use std::net::SocketAddr;
-use std::sync::{Arc, Mutex};
-use tokio::net::TcpListener;
-use tokio::prelude::*;
+use server::user::account::FileAccountStore;
+use std::io::{stdin, BufRead};
+use std::path::PathBuf;
+use structopt::StructOpt;
 
-const LOCALHOST: &str = "127.0.0.1";
-const PORT: &str = "47331";
+#[derive(Debug, StructOpt)]
+#[structopt(name = "server", about = "BombShell game server")]
+struct Opt {
+    /// TOML configuration file, the built-in defaults are used if not given
+    #[structopt(short, long, parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    /// Address to listen on, e.g. 127.0.0.1:47331
+    #[structopt(short, long)]
+    address: Option<String>,
+
+    /// Game loop ticks per second
+    #[structopt(short, long)]
+    tick_rate: Option<u32>,
+
+    /// Log level (error, warn, info, debug, trace), BB_LOG_LEVEL takes precedence
+    #[structopt(short, long)]
+    log_level: Option<String>,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Manages the accounts stored in the configured accounts file
+    Account(AccountCommand),
+}
+
+#[derive(Debug, StructOpt)]
+enum AccountCommand {
+    /// Creates a new account, the password is read from the standard input if not given
+    Add {
+        name: String,
+        #[structopt(short, long)]
+        password: Option<String>,
+    },
+    /// Changes the password of an account, the password is read from the standard input if not given
+    Passwd {
+        name: String,
+        #[structopt(short, long)]
+        password: Option<String>,
+    },
+    /// Removes an account
+    Remove { name: String },
+    /// Lists the name of every account
+    List,
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
-    let env = Env::default().filter_or("BB_LOG_LEVEL", "debug");
+    let opt = Opt::from_args();
+    let mut config = match &opt.config {
+        Some(path) => ServerConfig::from_file(path)?,
+        None => ServerConfig::default(),
+    };
+    if let Some(address) = opt.address {
+        config.address = address;
+    }
+    if let Some(tick_rate) = opt.tick_rate {
+        config.tick_rate = tick_rate;
+    }
+    if let Some(log_level) = opt.log_level {
+        config.log_level = log_level;
+    }
+    config.validate()?;
+
+    let env = Env::default().filter_or("BB_LOG_LEVEL", config.log_level.as_str());
     env_logger::init_from_env(env);
 
-    let addr = format!("{}:{}", LOCALHOST, PORT)
-        .parse::<SocketAddr>()
-        .map_err(|e| Error::new_network("Invalid local address"))?;
-    info!("Started server on {}:{}", LOCALHOST, PORT);
-    let mut server = TcpServer::new(addr);
-    server.start().await?;
+    match opt.command {
+        Some(Command::Account(command)) => manage_accounts(&config, command),
+        None => {
+            let mut server = TcpServer::new(config);
+            server.start().await
+        }
+    }
+}
+
+fn manage_accounts(config: &ServerConfig, command: AccountCommand) -> Result<(), Error> {
+    let mut store = FileAccountStore::open(&config.accounts_file)?;
+    match command {
+        AccountCommand::Add { name, password } => {
+            store.create_account(&name, &password_or_prompt(password)?)?;
+            println!("Account {} created", name);
+        }
+        AccountCommand::Passwd { name, password } => {
+            store.change_password(&name, &password_or_prompt(password)?)?;
+            println!("Password of {} changed", name);
+        }
+        AccountCommand::Remove { name } => {
+            store.remove_account(&name)?;
+            println!("Account {} removed", name);
+        }
+        AccountCommand::List => {
+            for name in store.account_names() {
+                println!("{}", name);
+            }
+        }
+    }
     Ok(())
 }
+
+fn password_or_prompt(password: Option<String>) -> Result<String, Error> {
+    if let Some(password) = password {
+        return Ok(password);
+    }
+    println!("Password:");
+    let mut line = String::new();
+    stdin()
+        .lock()
+        .read_line(&mut line)
+        .map_err(|e| Error::AccountError(e.to_string()))?;
+    let password = line.trim_end_matches(|c| c == '\r' || c == '\n').to_string();
+    if password.is_empty() {
+        return Err(Error::AccountError("Password must not be empty".to_string()));
+    }
+    Ok(password)
+}
@@ -1,20 +1,28 @@
 use legion::Entity;
 use std::fmt::{Display, Formatter};
 
+/// Compact identifier of a game object, that is used to reference it in the protocol.
+pub type NetworkId = u32;
+
 #[derive(Clone)]
 pub struct GameObjectIdentifier {
     pub internal: Entity,
     pub external: String,
+    pub network: NetworkId,
 }
 
 impl GameObjectIdentifier {
-    pub fn new(internal: Entity, external: String) -> Self {
-        GameObjectIdentifier { internal, external }
+    pub fn new(internal: Entity, external: String, network: NetworkId) -> Self {
+        GameObjectIdentifier {
+            internal,
+            external,
+            network,
+        }
     }
 }
 
 impl Display for GameObjectIdentifier {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "[{:#?} | {}]", self.internal, self.external)
+        write!(f, "[{:#?} | {} | {}]", self.internal, self.external, self.network)
     }
 }
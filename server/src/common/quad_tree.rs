@@ -1,303 +1,344 @@
-#![feature(drain_filter)]
-
 use crate::game::location::pos::{Area, Position, Positionable};
 use std::borrow::Borrow;
 use std::collections::HashMap;
-use std::slice::{Iter, IterMut};
-use std::sync::atomic::AtomicPtr;
+use std::hash::Hash;
+
+/// Checks whether the area of a node is responsible for the position. Positions outside of the
+/// bounds of the tree belong to the areas on the closest edge, so every position has a node.
+fn covers(bounds: &Area, area: &Area, pos: Position) -> bool {
+    let within = |p: f64, min: f64, max: f64, bound_min: f64, bound_max: f64| {
+        (p >= min || min <= bound_min) && (p < max || max >= bound_max)
+    };
+    within(
+        pos.x(),
+        area.min().x(),
+        area.max().x(),
+        bounds.min().x(),
+        bounds.max().x(),
+    ) && within(
+        pos.y(),
+        area.min().y(),
+        area.max().y(),
+        bounds.min().y(),
+        bounds.max().y(),
+    )
+}
+
+/// The area a node is responsible for. Nodes on the edges of the tree extend to infinity,
+/// as they store the positions outside of the bounds as well.
+fn reach(bounds: &Area, area: &Area) -> Area {
+    let extend = |v: f64, bound: f64, infinity: f64| if v == bound { infinity } else { v };
+    Area::from_point(
+        (
+            extend(area.min().x(), bounds.min().x(), f64::NEG_INFINITY),
+            extend(area.min().y(), bounds.min().y(), f64::NEG_INFINITY),
+        ),
+        (
+            extend(area.max().x(), bounds.max().x(), f64::INFINITY),
+            extend(area.max().y(), bounds.max().y(), f64::INFINITY),
+        ),
+    )
+}
+
+/// Children of a split node. A node stores the index of its first child in the arena, the
+/// rest follow it in this order.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Quadrant {
+    Third,
+    Second,
+    First,
+    Fourth,
+}
 
 #[derive(Debug)]
-pub struct QuadNode<T>
-where
-    T: Positionable,
-{
+pub struct QuadNode<K, T> {
     area: Area,
-    children: Vec<usize>,
-    values: HashMap<String, T>,
+    children: Option<usize>,
+    parent: Option<usize>,
+    values: HashMap<K, T>,
     depth: usize,
-    index: usize,
-    tree: AtomicPtr<QuadTree<T>>,
 }
 
-impl<T> QuadNode<T>
+impl<K, T> QuadNode<K, T>
 where
+    K: Hash + Eq + Clone,
     T: Positionable,
 {
-    pub fn new(
-        min: Position,
-        max: Position,
-        depth: usize,
-        index: usize,
-        tree: *mut QuadTree<T>,
-    ) -> Self {
+    fn new(area: Area, depth: usize, parent: Option<usize>) -> Self {
         QuadNode {
-            area: Area::from_point((min.x(), min.y()), (max.x(), max.y())),
-            children: Vec::new(),
+            area,
+            children: None,
+            parent,
             values: HashMap::new(),
             depth,
-            index,
-            tree: AtomicPtr::new(tree),
         }
     }
 
-    #[inline]
-    pub fn third_quadrant(&mut self) -> Option<&mut QuadNode<T>> {
-        let index = self.children.get(0);
-        if let Some(i) = index {
-            return unsafe { (*(*self.tree.get_mut())).arena.get_mut(*i) };
-        }
-
-        None
-    }
-
-    #[inline]
-    pub fn second_quadrant(&mut self) -> Option<&mut QuadNode<T>> {
-        let index = self.children.get(1);
-        if let Some(i) = index {
-            return unsafe { (*(*self.tree.get_mut())).arena.get_mut(*i) };
-        }
-
-        None
-    }
-
-    #[inline]
-    pub fn first_quadrant(&mut self) -> Option<&mut QuadNode<T>> {
-        let index = self.children.get(2);
-        if let Some(i) = index {
-            return unsafe { (*(*self.tree.get_mut())).arena.get_mut(*i) };
-        }
-
-        None
-    }
-
-    #[inline]
-    pub fn fourth_quadrant(&mut self) -> Option<&mut QuadNode<T>> {
-        let index = self.children.get(3);
-        if let Some(i) = index {
-            return unsafe { (*(*self.tree.get_mut())).arena.get_mut(*i) };
-        }
-
-        None
-    }
-
-    pub fn add_value(&mut self, k: String, v: T) {
-        if !self.is_leaf() {
-            return;
-        }
-
-        self.values.insert(k, v);
-    }
-
-    pub fn get_value(&mut self, k: String) -> Option<&mut T> {
-        self.values.get_mut(&k)
-    }
-
     pub fn get_area(&self) -> &Area {
         &self.area
     }
 
     pub fn is_leaf(&self) -> bool {
-        self.children.is_empty()
+        self.children.is_none()
     }
 
-    pub fn get_values(&mut self) -> &mut HashMap<String, T> {
-        &mut self.values
+    pub fn get_values(&self) -> &HashMap<K, T> {
+        &self.values
     }
 }
 
+/// Region quad tree. Nodes are stored in an arena and reference each other by index, so the
+/// tree can be moved freely. Children of emptied nodes are merged back into their parent.
 #[derive(Debug)]
-pub struct QuadTree<T>
-where
-    T: Positionable,
-{
-    arena: Vec<QuadNode<T>>,
+pub struct QuadTree<K, T> {
+    arena: Vec<QuadNode<K, T>>,
+    /// First indices of the child blocks released by merging, reused by the next split.
+    free: Vec<usize>,
     root: usize,
     bucket_size: usize,
     max_depth: usize,
-    object_node_map: HashMap<String, usize>,
+    object_node_map: HashMap<K, usize>,
 }
 
-impl<T> QuadTree<T>
+impl<K, T> QuadTree<K, T>
 where
+    K: Hash + Eq + Clone,
     T: Positionable,
 {
     pub fn new(min: Position, max: Position, bucket_size: usize, max_depth: usize) -> Self {
-        let mut tree = QuadTree {
-            arena: Vec::new(),
+        let area = Area::from_point((min.x(), min.y()), (max.x(), max.y()));
+        QuadTree {
+            arena: vec![QuadNode::new(area, 0, None)],
+            free: Vec::new(),
             root: 0,
             bucket_size,
             max_depth,
             object_node_map: HashMap::new(),
-        };
-        let mut tree_ptr = &mut tree as *mut QuadTree<T>;
-        tree.arena.push(QuadNode::new(min, max, 0, 0, tree_ptr));
-        tree
+        }
     }
 
-    pub fn get_root(&mut self) -> &mut QuadNode<T> {
-        self.arena.get_mut(self.root).unwrap()
+    pub fn get_root(&self) -> &QuadNode<K, T> {
+        &self.arena[self.root]
     }
 
-    pub fn find(&mut self, k: String) -> Option<&mut T> {
-        let node_id = self.object_node_map.get(&k);
-        if let Some(n) = node_id {
-            if let Some(node) = self.arena.get_mut(*n) {
-                return node.get_value(k.clone());
-            }
+    pub fn child(&self, node: &QuadNode<K, T>, quadrant: Quadrant) -> Option<&QuadNode<K, T>> {
+        node.children
+            .map(|first| &self.arena[first + quadrant as usize])
+    }
+
+    /// The area of the root node. Objects may be positioned outside of it, in which case they
+    /// are stored in the node on the closest edge.
+    pub fn bounds(&self) -> &Area {
+        &self.arena[self.root].area
+    }
+
+    pub fn len(&self) -> usize {
+        self.object_node_map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.object_node_map.is_empty()
+    }
+
+    /// Adds a value to the leaf responsible for its position. A value with the same key is
+    /// replaced.
+    pub fn add(&mut self, k: K, v: T) {
+        self.remove(&k);
+        let mut node_index = self.leaf_of(self.root, v.position());
+        while self.arena[node_index].values.len() >= self.bucket_size
+            && self.arena[node_index].depth < self.max_depth
+        {
+            self.split(node_index);
+            node_index = self.leaf_of(node_index, v.position());
         }
+        self.arena[node_index].values.insert(k.clone(), v);
+        self.object_node_map.insert(k, node_index);
+    }
 
-        None
+    pub fn get<Q>(&self, k: &Q) -> Option<&T>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let node_index = self.object_node_map.get(k)?;
+        self.arena[*node_index].values.get(k)
     }
 
-    pub fn add(&mut self, k: String, v: T) {
-        let mut node_candidate_index = self.root;
-        loop {
-            let node_candidate = self.arena.get(node_candidate_index).unwrap();
-            if node_candidate.is_leaf() {
-                break;
+    pub fn get_mut<Q>(&mut self, k: &Q) -> Option<&mut T>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let node_index = self.object_node_map.get(k)?;
+        self.arena[*node_index].values.get_mut(k)
+    }
+
+    /// Returns all values, that are positioned inside the area.
+    pub fn query_area(&self, area: &Area) -> Vec<&T> {
+        self.query(area, |pos| area.contains(pos))
+    }
+
+    /// Returns all values, that are positioned within the radius of the center.
+    pub fn query_radius(&self, center: Position, radius: f64) -> Vec<&T> {
+        self.query(&Area::around(center, radius), |pos| {
+            pos.distance_to(&center) <= radius
+        })
+    }
+
+    /// Collects the accepted values of every leaf, that intersects the area.
+    fn query<F>(&self, area: &Area, accept: F) -> Vec<&T>
+    where
+        F: Fn(Position) -> bool,
+    {
+        let bounds = self.bounds();
+        let mut result = Vec::new();
+        let mut nodes = vec![self.root];
+        while let Some(node_index) = nodes.pop() {
+            let node = &self.arena[node_index];
+            if !reach(bounds, &node.area).intersects(area) {
+                continue;
             }
-            for child_index in &node_candidate.children {
-                let mut child = self.arena.get(*child_index);
-                if let Some(child) = child {
-                    if child.get_area().contains(v.position()) {
-                        node_candidate_index = *child_index;
-                    }
-                }
+            match node.children {
+                Some(first) => nodes.extend(first..first + 4),
+                None => result.extend(node.values.values().filter(|v| accept(v.position()))),
             }
         }
+        result
+    }
 
-        let candidate_id = self.try_split(node_candidate_index, &v);
-        let mut node_candidate = self.arena.get_mut(candidate_id);
-        if let Some(node) = node_candidate {
-            node.add_value(k.clone(), v);
-            self.object_node_map.insert(k, candidate_id);
-        }
+    pub fn remove<Q>(&mut self, k: &Q) -> Option<T>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let node_index = self.object_node_map.remove(k)?;
+        let value = self.arena[node_index].values.remove(k);
+        self.merge(self.arena[node_index].parent);
+        value
     }
 
-    pub fn remove(&mut self, k: &str) -> Option<T> {
-        let mut res = None;
-        if let Some(n) = self.find_node_of_value(k) {
-            res = n.values.remove(k);
-        }
-        if res.is_some() {
-            self.object_node_map.remove(k);
+    pub fn find_node_of_value<Q>(&self, k: &Q) -> Option<&QuadNode<K, T>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.object_node_map
+            .get(k)
+            .map(|node_index| &self.arena[*node_index])
+    }
+
+    pub fn update_position<Q>(&mut self, k: &Q, new_pos: Position)
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+    {
+        let node_index = match self.object_node_map.get(k) {
+            Some(node_index) => *node_index,
+            None => return,
+        };
+        if covers(self.bounds(), &self.arena[node_index].area, new_pos) {
+            if let Some(value) = self.arena[node_index].values.get_mut(k) {
+                value.set_position(new_pos);
+            }
+        } else if let Some(mut value) = self.remove(k) {
+            value.set_position(new_pos);
+            self.add(k.to_owned(), value);
         }
-        res
     }
 
-    pub fn find_node_of_value(&mut self, k: &str) -> Option<&mut QuadNode<T>> {
-        if let Some(node_index) = self.object_node_map.get(k) {
-            self.arena.get_mut(*node_index)
-        } else {
-            None
+    /// Descends from the node to the leaf responsible for the position.
+    fn leaf_of(&self, node_index: usize, pos: Position) -> usize {
+        let bounds = self.bounds();
+        let mut node_index = node_index;
+        while let Some(first) = self.arena[node_index].children {
+            node_index = (first..first + 4)
+                .find(|child| covers(bounds, &self.arena[*child].area, pos))
+                .expect("Children cover the area of their parent");
         }
+        node_index
     }
 
-    pub fn update_position(&mut self, k: &str, new_pos: Position) {
-        if let Some(node_index) = self.object_node_map.get(k) {
-            if let Some(node) = self.arena.get_mut(*node_index) {
-                if node.area.contains(new_pos) {
-                    if let Some(value) = node.values.get_mut(k) {
-                        value.set_position(new_pos);
-                    }
-                } else {
-                    let mut val = self.remove(k);
-                    if let Some(mut val) = val.take() {
-                        val.set_position(new_pos);
-                        self.add(k.to_string(), val);
-                    }
+    /// Divides the leaf into four children and distributes its values among them.
+    fn split(&mut self, node_index: usize) {
+        let node = &self.arena[node_index];
+        let (min, max) = (node.area.min(), node.area.max());
+        let avg_x = ((min.x() + max.x()) / 2.0).floor();
+        let avg_y = ((min.y() + max.y()) / 2.0).floor();
+        let depth = node.depth + 1;
+        let parent = Some(node_index);
+        let children = [
+            Area::from_point((min.x(), min.y()), (avg_x, avg_y)),
+            Area::from_point((min.x(), avg_y), (avg_x, max.y())),
+            Area::from_point((avg_x, avg_y), (max.x(), max.y())),
+            Area::from_point((avg_x, min.y()), (max.x(), avg_y)),
+        ];
+
+        let first = match self.free.pop() {
+            Some(first) => {
+                for (i, area) in children.iter().enumerate() {
+                    self.arena[first + i] = QuadNode::new(area.clone(), depth, parent);
                 }
+                first
             }
+            None => {
+                let first = self.arena.len();
+                self.arena.extend(
+                    children
+                        .iter()
+                        .map(|area| QuadNode::new(area.clone(), depth, parent)),
+                );
+                first
+            }
+        };
+        self.arena[node_index].children = Some(first);
+
+        let values: Vec<(K, T)> = self.arena[node_index].values.drain().collect();
+        for (k, v) in values {
+            let child = self.leaf_of(node_index, v.position());
+            self.arena[child].values.insert(k.clone(), v);
+            self.object_node_map.insert(k, child);
         }
     }
 
-    pub fn iter_mut(&mut self) -> IterMut<QuadNode<T>> {
-        self.arena.iter_mut()
-    }
-
-    pub fn iter(&self) -> Iter<QuadNode<T>> {
-        self.arena.iter()
-    }
-
-    fn try_split(&mut self, node_id: usize, v: &T) -> usize {
-        let mut new_children = Vec::new();
-        let current_max_node_id = self.arena.len() - 1;
-        let mut selected_node_id = node_id;
-        let mut values: HashMap<String, T> = HashMap::new();
-        let mut tree_ptr = self as *mut QuadTree<T>;
-        let mut node = self.arena.get_mut(node_id);
-        if let Some(node) = node {
-            if node.values.len() >= self.bucket_size && node.depth < self.max_depth {
-                let min_x = node.area.min().x();
-                let min_y = node.area.min().y();
-                let max_x = node.area.max().x();
-                let max_y = node.area.max().y();
-
-                let avg_x = ((node.area.min().x() + node.area.max().x()) / 2 as f64).floor();
-                let avg_y = ((node.area.min().y() + node.area.max().y()) / 2 as f64).floor();
-                values.extend(node.values.drain());
-
-                new_children.push(QuadNode::new(
-                    Position::from_coord(min_x, min_y),
-                    Position::from_coord(avg_x, avg_y),
-                    node.depth + 1,
-                    current_max_node_id + 1,
-                    tree_ptr.clone(),
-                ));
-                new_children.push(QuadNode::new(
-                    Position::from_coord(min_x, avg_y),
-                    Position::from_coord(avg_x, max_y),
-                    node.depth + 1,
-                    current_max_node_id + 2,
-                    tree_ptr.clone(),
-                ));
-                new_children.push(QuadNode::new(
-                    Position::from_coord(avg_x, avg_y),
-                    Position::from_coord(max_x, max_y),
-                    node.depth + 1,
-                    current_max_node_id + 3,
-                    tree_ptr.clone(),
-                ));
-                new_children.push(QuadNode::new(
-                    Position::from_coord(avg_x, min_y),
-                    Position::from_coord(max_x, avg_y),
-                    node.depth + 1,
-                    current_max_node_id + 4,
-                    tree_ptr.clone(),
-                ));
-                node.children
-                    .extend(current_max_node_id + 1..=current_max_node_id + 4);
+    /// Merges the children of the node and its ancestors back, as long as their values fit
+    /// into a single bucket.
+    fn merge(&mut self, node_index: Option<usize>) {
+        let mut node_index = node_index;
+        while let Some(index) = node_index {
+            let first = match self.arena[index].children {
+                Some(first) => first,
+                None => return,
+            };
+            let children = first..first + 4;
+            let mergeable = children.clone().all(|child| self.arena[child].is_leaf());
+            let count: usize = children
+                .clone()
+                .map(|child| self.arena[child].values.len())
+                .sum();
+            if !mergeable || count >= self.bucket_size {
+                return;
             }
-        }
-        let keys: Vec<String> = values.keys().map(|k| k.clone()).collect();
-        for child in &mut new_children {
-            if child.get_area().contains(v.position()) {
-                selected_node_id = child.index;
-            }
-            for object_id in &keys {
-                let value = values.get(object_id);
-                let mut found = false;
-                if let Some(v) = value {
-                    if child.get_area().contains(v.position()) {
-                        found = true;
-                    }
-                }
-                drop(value);
-                if found {
-                    child.add_value(object_id.clone(), values.remove(object_id).unwrap());
-                    self.object_node_map.insert(object_id.clone(), child.index);
+
+            for child in children {
+                let values: Vec<(K, T)> = self.arena[child].values.drain().collect();
+                for (k, v) in values {
+                    self.arena[index].values.insert(k.clone(), v);
+                    self.object_node_map.insert(k, index);
                 }
             }
+            self.arena[index].children = None;
+            self.free.push(first);
+            node_index = self.arena[index].parent;
         }
-        self.arena.extend(new_children);
-        selected_node_id
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::common::quad_tree::QuadTree;
-    use crate::game::location::pos::{Position, Positionable};
+    use crate::common::quad_tree::{Quadrant, QuadTree};
+    use crate::game::location::pos::{Area, Position, Positionable};
+    use proptest::prelude::*;
+    use std::collections::HashMap;
 
     #[derive(Debug)]
     pub struct TestPosition {
@@ -311,13 +352,36 @@ mod tests {
         }
 
         fn set_position(&mut self, new_position: Position) {
-            todo!()
+            self.x = new_position.x();
+            self.y = new_position.y();
         }
     }
 
+    /// A tree, which root is split into four leaves by the third value.
+    fn split_tree() -> QuadTree<String, TestPosition> {
+        let mut tree: QuadTree<String, TestPosition> = QuadTree::new(
+            Position::from_coord(0 as f64, 0 as f64),
+            Position::from_coord(1000 as f64, 1000 as f64),
+            2,
+            1,
+        );
+        tree.add("1".to_string(), TestPosition { x: 100.0, y: 100.0 });
+        tree.add("2".to_string(), TestPosition { x: 600.0, y: 100.0 });
+        tree.add("3".to_string(), TestPosition { x: 100.0, y: 600.0 });
+        assert!(!tree.get_root().is_leaf());
+        tree
+    }
+
+    fn node_min(tree: &QuadTree<String, TestPosition>, k: &str) -> (f64, f64) {
+        let node = tree.find_node_of_value(k).expect("Value has a node");
+        assert!(node.get_values().contains_key(k));
+        let min = node.get_area().min();
+        (min.x(), min.y())
+    }
+
     #[test]
     fn test_split_after_bucket_size_limit_reached() {
-        let mut tree: QuadTree<TestPosition> = QuadTree::new(
+        let mut tree: QuadTree<String, TestPosition> = QuadTree::new(
             Position::from_coord(0 as f64, 0 as f64),
             Position::from_coord(1000 as f64, 1000 as f64),
             5,
@@ -330,31 +394,25 @@ mod tests {
         tree.add("5".to_string(), TestPosition { x: 100.0, y: 900.0 });
 
         assert!(tree.get_root().is_leaf());
-        assert_eq!(tree.get_root().children.len(), 0);
+        assert!(tree.get_root().children.is_none());
 
         tree.add("6".to_string(), TestPosition { x: 900.0, y: 900.0 });
 
-        let mut root = tree.get_root();
+        let root = tree.get_root();
         assert!(!root.is_leaf());
-        {
-            let mut third = root.third_quadrant().unwrap();
-            assert_eq!(third.values.len(), 1);
-        }
-        {
-            let mut second = root.second_quadrant().unwrap();
-            assert_eq!(second.values.len(), 2);
-        }
-        {
-            let mut first = root.first_quadrant().unwrap();
-            assert_eq!(first.values.len(), 2);
-        }
-        let mut fourth = root.fourth_quadrant().unwrap();
+        let third = tree.child(root, Quadrant::Third).unwrap();
+        assert_eq!(third.values.len(), 1);
+        let second = tree.child(root, Quadrant::Second).unwrap();
+        assert_eq!(second.values.len(), 2);
+        let first = tree.child(root, Quadrant::First).unwrap();
+        assert_eq!(first.values.len(), 2);
+        let fourth = tree.child(root, Quadrant::Fourth).unwrap();
         assert_eq!(fourth.values.len(), 1);
     }
 
     #[test]
     fn test_find_element() {
-        let mut tree: QuadTree<TestPosition> = QuadTree::new(
+        let mut tree: QuadTree<String, TestPosition> = QuadTree::new(
             Position::from_coord(0 as f64, 0 as f64),
             Position::from_coord(1000 as f64, 1000 as f64),
             5,
@@ -364,7 +422,7 @@ mod tests {
         tree.add("2".to_string(), TestPosition { x: 500.0, y: 500.0 });
         tree.add("3".to_string(), TestPosition { x: 700.0, y: 300.0 });
 
-        let mut element = tree.find("3".to_string());
+        let element = tree.get_mut("3");
         if let Some(e) = element {
             assert_eq!(700.0, e.x);
             assert_eq!(300.0, e.y);
@@ -375,7 +433,7 @@ mod tests {
 
     #[test]
     fn test_remove_element() {
-        let mut tree: QuadTree<TestPosition> = QuadTree::new(
+        let mut tree: QuadTree<String, TestPosition> = QuadTree::new(
             Position::from_coord(0 as f64, 0 as f64),
             Position::from_coord(1000 as f64, 1000 as f64),
             5,
@@ -387,9 +445,192 @@ mod tests {
 
         tree.remove("3");
 
-        let mut element = tree.find("3".to_string());
+        let element = tree.get_mut("3");
         if let Some(e) = element {
             panic!("Element is found, but it was removed before");
         }
     }
+
+    #[test]
+    fn test_update_position_crosses_quadrants() {
+        let mut tree = split_tree();
+        assert_eq!(node_min(&tree, "1"), (0.0, 0.0));
+
+        tree.update_position("1", Position::from_coord(900.0, 900.0));
+        assert_eq!(node_min(&tree, "1"), (500.0, 500.0));
+
+        tree.update_position("1", Position::from_coord(499.0, 501.0));
+        assert_eq!(node_min(&tree, "1"), (0.0, 500.0));
+
+        let element = tree.get_mut("1").expect("Moved element is found");
+        assert_eq!((element.x, element.y), (499.0, 501.0));
+        assert_eq!(node_min(&tree, "2"), (500.0, 0.0));
+    }
+
+    #[test]
+    fn test_update_position_within_quadrant() {
+        let mut tree = split_tree();
+        tree.update_position("2", Position::from_coord(999.0, 499.0));
+
+        assert_eq!(node_min(&tree, "2"), (500.0, 0.0));
+        let element = tree.get_mut("2").expect("Element is found");
+        assert_eq!((element.x, element.y), (999.0, 499.0));
+    }
+
+    #[test]
+    fn test_position_out_of_bounds() {
+        let mut tree = split_tree();
+        tree.update_position("1", Position::from_coord(-50.0, 1200.0));
+        assert_eq!(node_min(&tree, "1"), (0.0, 500.0));
+
+        tree.add("4".to_string(), TestPosition { x: 1000.0, y: -1.0 });
+        assert_eq!(node_min(&tree, "4"), (500.0, 0.0));
+    }
+
+    #[test]
+    fn test_query_radius_across_nodes() {
+        let mut tree = split_tree();
+        tree.add("4".to_string(), TestPosition { x: 510.0, y: 100.0 });
+        tree.add("5".to_string(), TestPosition { x: 490.0, y: 490.0 });
+
+        let mut found: Vec<(f64, f64)> = tree
+            .query_radius(Position::from_coord(490.0, 100.0), 30.0)
+            .iter()
+            .map(|v| (v.x, v.y))
+            .collect();
+        found.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(found, vec![(510.0, 100.0)]);
+
+        let found = tree.query_radius(Position::from_coord(500.0, 500.0), 15.0);
+        assert_eq!(found.len(), 1);
+        assert_eq!((found[0].x, found[0].y), (490.0, 490.0));
+    }
+
+    #[test]
+    fn test_query_area() {
+        let mut tree = split_tree();
+        tree.add("4".to_string(), TestPosition { x: -20.0, y: 50.0 });
+
+        let found = tree.query_area(&Area::from_point((-50.0, 0.0), (650.0, 200.0)));
+        let mut found: Vec<(f64, f64)> = found.iter().map(|v| (v.x, v.y)).collect();
+        found.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(found, vec![(-20.0, 50.0), (100.0, 100.0), (600.0, 100.0)]);
+
+        assert!(tree
+            .query_area(&Area::from_point((700.0, 700.0), (800.0, 800.0)))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_merge_after_remove() {
+        let mut tree = split_tree();
+        tree.remove("3");
+        assert!(!tree.get_root().is_leaf());
+
+        tree.remove("2");
+        assert!(tree.get_root().is_leaf());
+        assert_eq!(node_min(&tree, "1"), (0.0, 0.0));
+        assert_eq!(tree.len(), 1);
+
+        tree.add("2".to_string(), TestPosition { x: 600.0, y: 100.0 });
+        tree.add("3".to_string(), TestPosition { x: 100.0, y: 600.0 });
+        assert_eq!(node_min(&tree, "3"), (0.0, 500.0));
+        assert_eq!(tree.arena.len(), 5);
+    }
+
+    #[derive(Clone, Debug)]
+    enum Operation {
+        Add(u32, f64, f64),
+        Remove(u32),
+        Move(u32, f64, f64),
+    }
+
+    fn coordinate() -> impl Strategy<Value = f64> {
+        (-100..1100).prop_map(f64::from)
+    }
+
+    fn operation() -> impl Strategy<Value = Operation> {
+        prop_oneof![
+            (0..40u32, coordinate(), coordinate()).prop_map(|(k, x, y)| Operation::Add(k, x, y)),
+            (0..40u32).prop_map(Operation::Remove),
+            (0..40u32, coordinate(), coordinate()).prop_map(|(k, x, y)| Operation::Move(k, x, y)),
+        ]
+    }
+
+    /// Applies the operations both on a tree and on a plain list of positions.
+    fn build(operations: &[Operation]) -> (QuadTree<u32, TestPosition>, HashMap<u32, (f64, f64)>) {
+        let mut tree = QuadTree::new(
+            Position::from_coord(0.0, 0.0),
+            Position::from_coord(1000.0, 1000.0),
+            3,
+            5,
+        );
+        let mut positions = HashMap::new();
+        for operation in operations {
+            match *operation {
+                Operation::Add(k, x, y) => {
+                    tree.add(k, TestPosition { x, y });
+                    positions.insert(k, (x, y));
+                }
+                Operation::Remove(k) => {
+                    assert_eq!(tree.remove(&k).is_some(), positions.remove(&k).is_some());
+                }
+                Operation::Move(k, x, y) => {
+                    tree.update_position(&k, Position::from_coord(x, y));
+                    if let Some(position) = positions.get_mut(&k) {
+                        *position = (x, y);
+                    }
+                }
+            }
+        }
+        (tree, positions)
+    }
+
+    fn sorted(mut found: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+        found.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        found
+    }
+
+    proptest! {
+        #[test]
+        fn prop_query_radius_matches_brute_force(
+            operations in prop::collection::vec(operation(), 0..200),
+            (x, y) in (coordinate(), coordinate()),
+            radius in 0.0..400.0f64,
+        ) {
+            let (tree, positions) = build(&operations);
+            let center = Position::from_coord(x, y);
+
+            let found = tree.query_radius(center, radius).iter().map(|v| (v.x, v.y)).collect();
+            let expected = positions
+                .values()
+                .filter(|(x, y)| Position::from_coord(*x, *y).distance_to(&center) <= radius)
+                .cloned()
+                .collect();
+            prop_assert_eq!(sorted(found), sorted(expected));
+        }
+
+        #[test]
+        fn prop_query_area_matches_brute_force(
+            operations in prop::collection::vec(operation(), 0..200),
+            (x, y) in (coordinate(), coordinate()),
+            (width, height) in (0.0..600.0f64, 0.0..600.0f64),
+        ) {
+            let (tree, positions) = build(&operations);
+            let area = Area::from_point((x, y), (x + width, y + height));
+
+            let found = tree.query_area(&area).iter().map(|v| (v.x, v.y)).collect();
+            let expected = positions
+                .values()
+                .filter(|(x, y)| area.contains(Position::from_coord(*x, *y)))
+                .cloned()
+                .collect();
+            prop_assert_eq!(sorted(found), sorted(expected));
+            prop_assert_eq!(tree.len(), positions.len());
+            for (k, (x, y)) in &positions {
+                let value = tree.get(k).expect("Value is stored");
+                prop_assert_eq!((value.x, value.y), (*x, *y));
+            }
+        }
+    }
 }
@@ -0,0 +1,447 @@
+use crate::error::error::Error;
+use crate::game::location::pos::{Area, Position};
+use crate::net::packet::batch::BATCH_HEADER_SIZE;
+use crate::net::protocol::frame::MAX_FRAME_SIZE;
+use serde::Deserialize;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Server settings, that can be loaded from a TOML file. Missing keys fall back to the defaults.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    pub address: String,
+    pub tick_rate: u32,
+    pub log_level: String,
+    pub accounts_file: PathBuf,
+    pub heartbeat: HeartbeatConfig,
+    pub session: SessionConfig,
+    pub network: NetworkConfig,
+    pub quad_tree: QuadTreeConfig,
+    pub instances: InstanceConfig,
+    /// Radius around a player, in which other objects are visible to it.
+    pub view_distance: f64,
+    pub default_zone: String,
+    pub zones: Vec<ZoneConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct HeartbeatConfig {
+    pub timeout_secs: u64,
+    pub ping_interval_secs: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SessionConfig {
+    pub grace_period_secs: u64,
+    pub duplicate_login: DuplicateLoginPolicy,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// Packets of a tick are batched into frames of at most this many bytes.
+    pub max_batch_size: usize,
+}
+
+/// Decides what happens, when an account logs in while it is already connected.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateLoginPolicy {
+    /// The previous connection is dropped and the new one takes over the player.
+    KickOld,
+    /// The new login is refused.
+    RejectNew,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct QuadTreeConfig {
+    pub bucket_size: usize,
+    pub max_depth: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct InstanceConfig {
+    /// Empty instances are torn down after this many seconds
+    pub teardown_secs: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ZoneConfig {
+    pub id: String,
+    pub min: (f64, f64),
+    pub max: (f64, f64),
+    /// The zone is a template, every party entering it gets its own copy
+    #[serde(default)]
+    pub instanced: bool,
+    /// Tile map of the collision, the whole zone is walkable without it
+    #[serde(default)]
+    pub map: Option<PathBuf>,
+    #[serde(default)]
+    pub portals: Vec<PortalConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PortalConfig {
+    pub min: (f64, f64),
+    pub max: (f64, f64),
+    /// Zone the portal leads to
+    pub destination: String,
+    /// Arrival position in the destination zone
+    pub spawn: (f64, f64),
+}
+
+impl ServerConfig {
+    /// Reads and parses a TOML configuration file.
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let content = fs::read_to_string(path).map_err(|e| {
+            Error::ConfigError(format!("Unable to read {}: {}", path.display(), e))
+        })?;
+        ServerConfig::from_toml(&content)
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self, Error> {
+        let config: ServerConfig =
+            toml::from_str(content).map_err(|e| Error::ConfigError(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn socket_addr(&self) -> Result<SocketAddr, Error> {
+        self.address
+            .parse::<SocketAddr>()
+            .map_err(|_| Error::ConfigError(format!("Invalid address {}", self.address)))
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.tick_rate == 0 {
+            return Err(Error::new_config("Tick rate must be greater than 0"));
+        }
+        if self.quad_tree.bucket_size == 0 {
+            return Err(Error::new_config("Quad tree bucket size must be greater than 0"));
+        }
+        if self.network.max_batch_size <= BATCH_HEADER_SIZE {
+            return Err(Error::ConfigError(format!(
+                "Max batch size must be greater than {}",
+                BATCH_HEADER_SIZE
+            )));
+        }
+        // Clients drop larger frames as a protocol error
+        if self.network.max_batch_size > MAX_FRAME_SIZE {
+            return Err(Error::ConfigError(format!(
+                "Max batch size must not be greater than {}",
+                MAX_FRAME_SIZE
+            )));
+        }
+        if !self.view_distance.is_finite() || self.view_distance <= 0.0 {
+            return Err(Error::new_config("View distance must be greater than 0"));
+        }
+        match self.zones.iter().find(|z| z.id == self.default_zone) {
+            None => {
+                return Err(Error::ConfigError(format!(
+                    "Default zone {} is not defined",
+                    self.default_zone
+                )))
+            }
+            Some(zone) if zone.instanced => {
+                return Err(Error::ConfigError(format!(
+                    "Default zone {} can not be instanced",
+                    self.default_zone
+                )))
+            }
+            _ => (),
+        }
+        for zone in &self.zones {
+            if zone.min.0 >= zone.max.0 || zone.min.1 >= zone.max.1 {
+                return Err(Error::ConfigError(format!(
+                    "Zone {} has invalid bounds",
+                    zone.id
+                )));
+            }
+            for portal in &zone.portals {
+                self.validate_portal(zone, portal)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ServerConfig {
+    fn validate_portal(&self, zone: &ZoneConfig, portal: &PortalConfig) -> Result<(), Error> {
+        if portal.min.0 >= portal.max.0 || portal.min.1 >= portal.max.1 {
+            return Err(Error::ConfigError(format!(
+                "Portal of zone {} has invalid bounds",
+                zone.id
+            )));
+        }
+        let destination = self
+            .zones
+            .iter()
+            .find(|z| z.id == portal.destination)
+            .ok_or_else(|| {
+                Error::ConfigError(format!(
+                    "Portal of zone {} leads to the undefined zone {}",
+                    zone.id, portal.destination
+                ))
+            })?;
+        // Arriving inside another portal would move the object back and forth
+        if destination
+            .portals
+            .iter()
+            .any(|p| p.area().contains(Position::from_coord(portal.spawn.0, portal.spawn.1)))
+        {
+            return Err(Error::ConfigError(format!(
+                "Portal of zone {} spawns into a portal of zone {}",
+                zone.id, portal.destination
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl ZoneConfig {
+    pub fn area(&self) -> Area {
+        Area::from_point(self.min, self.max)
+    }
+}
+
+impl PortalConfig {
+    pub fn area(&self) -> Area {
+        Area::from_point(self.min, self.max)
+    }
+}
+
+impl HeartbeatConfig {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs)
+    }
+
+    pub fn ping_interval(&self) -> Duration {
+        Duration::from_secs(self.ping_interval_secs)
+    }
+}
+
+impl InstanceConfig {
+    pub fn teardown(&self) -> Duration {
+        Duration::from_secs(self.teardown_secs)
+    }
+}
+
+impl SessionConfig {
+    pub fn grace_period(&self) -> Duration {
+        Duration::from_secs(self.grace_period_secs)
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            address: "127.0.0.1:47331".to_string(),
+            tick_rate: 1,
+            log_level: "debug".to_string(),
+            accounts_file: PathBuf::from("accounts.db"),
+            heartbeat: HeartbeatConfig::default(),
+            session: SessionConfig::default(),
+            network: NetworkConfig::default(),
+            quad_tree: QuadTreeConfig::default(),
+            instances: InstanceConfig::default(),
+            view_distance: 100.0,
+            default_zone: "1".to_string(),
+            zones: vec![ZoneConfig {
+                id: "1".to_string(),
+                min: (0.0, 0.0),
+                max: (1000.0, 1000.0),
+                instanced: false,
+                map: None,
+                portals: Vec::new(),
+            }],
+        }
+    }
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        HeartbeatConfig {
+            timeout_secs: 30,
+            ping_interval_secs: 5,
+        }
+    }
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        SessionConfig {
+            grace_period_secs: 60,
+            duplicate_login: DuplicateLoginPolicy::KickOld,
+        }
+    }
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        NetworkConfig {
+            max_batch_size: 1400,
+        }
+    }
+}
+
+impl Default for InstanceConfig {
+    fn default() -> Self {
+        InstanceConfig { teardown_secs: 300 }
+    }
+}
+
+impl Default for QuadTreeConfig {
+    fn default() -> Self {
+        QuadTreeConfig {
+            bucket_size: 50,
+            max_depth: 4,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::config::{DuplicateLoginPolicy, ServerConfig};
+    use std::time::Duration;
+
+    #[test]
+    fn test_partial_config() {
+        let config = ServerConfig::from_toml(
+            r#"
+            address = "0.0.0.0:5000"
+            tick_rate = 20
+            default_zone = "town"
+
+            [quad_tree]
+            bucket_size = 10
+
+            [[zones]]
+            id = "town"
+            min = [0.0, 0.0]
+            max = [500.0, 250.0]
+            "#,
+        )
+        .expect("Valid config");
+
+        assert_eq!(config.socket_addr().unwrap().port(), 5000);
+        assert_eq!(config.tick_rate, 20);
+        assert_eq!(config.log_level, "debug");
+        assert_eq!(config.quad_tree.bucket_size, 10);
+        assert_eq!(config.quad_tree.max_depth, 4);
+        assert_eq!(config.network.max_batch_size, 1400);
+        assert_eq!(config.zones.len(), 1);
+        assert_eq!(config.zones[0].max, (500.0, 250.0));
+        assert_eq!(config.session.duplicate_login, DuplicateLoginPolicy::KickOld);
+    }
+
+    #[test]
+    fn test_duplicate_login_policy() {
+        let config = ServerConfig::from_toml(
+            r#"
+            [session]
+            duplicate_login = "reject_new"
+            "#,
+        )
+        .expect("Valid config");
+        assert_eq!(config.session.duplicate_login, DuplicateLoginPolicy::RejectNew);
+
+        let config = ServerConfig::from_toml(
+            r#"
+            [session]
+            duplicate_login = "ignore"
+            "#,
+        );
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn test_max_batch_size() {
+        for size in &[10, 65537] {
+            let config =
+                ServerConfig::from_toml(&format!("[network]\nmax_batch_size = {}", size));
+            assert!(config.is_err(), "Max batch size {} is accepted", size);
+        }
+        let config = ServerConfig::from_toml("[network]\nmax_batch_size = 65536");
+        assert_eq!(config.unwrap().network.max_batch_size, 65536);
+    }
+
+    #[test]
+    fn test_undefined_default_zone() {
+        let config = ServerConfig::from_toml(r#"default_zone = "missing""#);
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn test_portals() {
+        let zones = r#"
+            [[zones]]
+            id = "1"
+            min = [0.0, 0.0]
+            max = [100.0, 100.0]
+
+            [[zones.portals]]
+            min = [90.0, 0.0]
+            max = [100.0, 10.0]
+            destination = "2"
+            spawn = [5.0, 5.0]
+
+            [[zones]]
+            id = "2"
+            min = [0.0, 0.0]
+            max = [100.0, 100.0]
+            "#;
+        let config = ServerConfig::from_toml(zones).expect("Valid config");
+        assert_eq!(config.zones[0].portals[0].destination, "2");
+        assert!(config.zones[1].portals.is_empty());
+
+        let undefined = ServerConfig::from_toml(&zones.replace(r#"destination = "2""#, r#"destination = "3""#));
+        assert!(undefined.is_err());
+
+        let spawn_in_portal = ServerConfig::from_toml(&format!(
+            "{}{}",
+            zones,
+            r#"
+            [[zones.portals]]
+            min = [0.0, 0.0]
+            max = [10.0, 10.0]
+            destination = "1"
+            spawn = [50.0, 50.0]
+            "#
+        ));
+        assert!(spawn_in_portal.is_err());
+    }
+
+    #[test]
+    fn test_instanced_zones() {
+        let zones = r#"
+            [instances]
+            teardown_secs = 60
+
+            [[zones]]
+            id = "1"
+            min = [0.0, 0.0]
+            max = [100.0, 100.0]
+
+            [[zones]]
+            id = "dungeon"
+            min = [0.0, 0.0]
+            max = [100.0, 100.0]
+            instanced = true
+            "#;
+        let config = ServerConfig::from_toml(zones).expect("Valid config");
+        assert_eq!(config.instances.teardown(), Duration::from_secs(60));
+        assert!(!config.zones[0].instanced);
+        assert!(config.zones[1].instanced);
+
+        let instanced_default =
+            ServerConfig::from_toml(&format!("default_zone = \"dungeon\"\n{}", zones));
+        assert!(instanced_default.is_err());
+    }
+}
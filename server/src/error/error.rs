@@ -4,24 +4,51 @@ use std::fmt::{Display, Formatter};
 pub enum Error {
     NetworkError(String),
     AuthError(AuthError),
+    ConfigError(String),
+    AccountError(String),
+    MapError(String),
 }
 
 impl Error {
     pub fn new_network(msg: &str) -> Self {
         Error::NetworkError(msg.to_string())
     }
+
+    pub fn new_config(msg: &str) -> Self {
+        Error::ConfigError(msg.to_string())
+    }
 }
 
 #[derive(Debug, Eq, PartialEq)]
 #[repr(u8)]
 pub enum AuthError {
     INVALID_USER,
+    UNEXPECTED_PACKET,
+    TIMEOUT,
+    INVALID_SESSION,
+    DUPLICATE_LOGIN,
 }
 
 impl AuthError {
     pub fn invalid_user_or_password() -> Error {
         Error::AuthError(AuthError::INVALID_USER)
     }
+
+    pub fn unexpected_packet() -> Error {
+        Error::AuthError(AuthError::UNEXPECTED_PACKET)
+    }
+
+    pub fn timeout() -> Error {
+        Error::AuthError(AuthError::TIMEOUT)
+    }
+
+    pub fn invalid_session() -> Error {
+        Error::AuthError(AuthError::INVALID_SESSION)
+    }
+
+    pub fn duplicate_login() -> Error {
+        Error::AuthError(AuthError::DUPLICATE_LOGIN)
+    }
 }
 
 impl Display for AuthError {
@@ -30,6 +57,10 @@ impl Display for AuthError {
             f,
             "{}", match self {
                 AuthError::INVALID_USER => "Invalid user or password",
+                AuthError::UNEXPECTED_PACKET => "Unexpected packet during authentication",
+                AuthError::TIMEOUT => "Authentication timed out",
+                AuthError::INVALID_SESSION => "Invalid or expired session token",
+                AuthError::DUPLICATE_LOGIN => "The account is already logged in",
             }
         )
     }
@@ -43,6 +74,9 @@ impl Display for Error {
             match self {
                 Error::NetworkError(s) => format!("NetworkError: {}", s),
                 Error::AuthError(e) => e.to_string(),
+                Error::ConfigError(s) => format!("ConfigError: {}", s),
+                Error::AccountError(s) => format!("AccountError: {}", s),
+                Error::MapError(s) => format!("MapError: {}", s),
             }
         )
     }
@@ -0,0 +1,2 @@
+pub mod movement;
+pub mod npc;
\ No newline at end of file
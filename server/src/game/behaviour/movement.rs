@@ -0,0 +1,386 @@
+use crate::game::components::movement::{Location, Transformation};
+use crate::game::components::state::{State, StateData, StateMachine};
+use crate::game::location::facing::TurnDirection;
+use crate::game::location::pos::Position;
+use crate::game::map::zone::Zone;
+use crate::net::data::PlayerInputAction;
+use crate::net::packet::state_delta::{ObjectStateBatch, ObjectStateChange};
+use std::f32::consts::{FRAC_PI_2, PI};
+use std::time::Duration;
+
+pub const MOVEMENT_MACHINE: &str = "movement";
+
+/// State machine of objects, that move by player input.
+pub struct Movable;
+
+impl StateData for Movable {
+    type Data<'a> = MovableStateData<'a>;
+}
+
+pub struct MovableStateData<'a> {
+    pub transformation: &'a mut Transformation,
+    pub location: &'a mut Location,
+    /// Zone of the object, that restricts its movement.
+    pub zone: Option<&'a Zone>,
+    pub delta: Duration,
+    pub action: Option<PlayerInputAction>,
+    pub state_delta: ObjectStateBatch,
+}
+
+impl<'a> MovableStateData<'a> {
+    pub fn new(
+        transformation: &'a mut Transformation,
+        location: &'a mut Location,
+        zone: Option<&'a Zone>,
+        delta: Duration,
+        action: Option<PlayerInputAction>,
+    ) -> Self {
+        MovableStateData {
+            transformation,
+            location,
+            zone,
+            delta,
+            action,
+            state_delta: ObjectStateBatch::new(),
+        }
+    }
+}
+
+/// Standing and moving are sub states of `movable`, which handles turning in both of them.
+pub fn movement_machine() -> StateMachine<Movable> {
+    StateMachine::builder(MOVEMENT_MACHINE)
+        .state(MovableState)
+        .sub_state("movable", IdleState)
+        .sub_state(
+            "movable",
+            MoveState {
+                direction: MoveDirection::Forward,
+            },
+        )
+        .initial("idle")
+        .transition("idle", "moving", starts_moving)
+        .transition("moving", "idle", stops_moving)
+        .build()
+}
+
+fn starts_moving(data: &MovableStateData) -> bool {
+    MoveDirection::from_action(data.action).is_some()
+}
+
+fn stops_moving(data: &MovableStateData) -> bool {
+    data.action == Some(PlayerInputAction::StopMove)
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum MoveDirection {
+    Forward,
+    Backward,
+    StrafeLeft,
+    StrafeRight,
+}
+
+impl MoveDirection {
+    fn from_action(action: Option<PlayerInputAction>) -> Option<Self> {
+        match action {
+            Some(PlayerInputAction::MoveForward) => Some(MoveDirection::Forward),
+            Some(PlayerInputAction::MoveBackward) => Some(MoveDirection::Backward),
+            Some(PlayerInputAction::StrafeLeft) => Some(MoveDirection::StrafeLeft),
+            Some(PlayerInputAction::StrafeRight) => Some(MoveDirection::StrafeRight),
+            _ => None,
+        }
+    }
+
+    /// Angle of the movement relative to the facing.
+    fn relative_angle(&self) -> f32 {
+        match self {
+            MoveDirection::Forward => 0.0,
+            MoveDirection::Backward => PI,
+            MoveDirection::StrafeLeft => FRAC_PI_2,
+            MoveDirection::StrafeRight => -FRAC_PI_2,
+        }
+    }
+}
+
+/// Applies the turning actions and the ongoing turn to the facing. Turning is independent of
+/// moving, so it is handled the same way in every movement state.
+struct MovableState;
+
+impl State<Movable> for MovableState {
+    fn name(&self) -> &'static str {
+        "movable"
+    }
+
+    fn update(&mut self, data: &mut MovableStateData) {
+        let transformation = &mut *data.transformation;
+        let mut changed = false;
+        match data.action {
+            Some(PlayerInputAction::TurnLeft) => {
+                transformation.turning = Some(TurnDirection::Left)
+            }
+            Some(PlayerInputAction::TurnRight) => {
+                transformation.turning = Some(TurnDirection::Right)
+            }
+            Some(PlayerInputAction::StopTurn) => transformation.turning = None,
+            Some(PlayerInputAction::SetFacing(angle)) => {
+                transformation.turning = None;
+                transformation.facing.set(angle);
+                changed = true;
+            }
+            _ => (),
+        };
+        if let Some(turning) = transformation.turning {
+            let angle = transformation.turn_rate * data.delta.as_secs_f32();
+            match turning {
+                TurnDirection::Left => transformation.facing.turn(angle),
+                TurnDirection::Right => transformation.facing.turn(-angle),
+            };
+            changed = true;
+        }
+        if changed {
+            data.state_delta
+                .add(ObjectStateChange::Facing(transformation.facing.get_facing()));
+        }
+    }
+
+    fn on_enter(&mut self, _data: &mut MovableStateData) {
+        debug!("STARTED MOVABLE");
+    }
+}
+
+struct IdleState;
+
+impl State<Movable> for IdleState {
+    fn name(&self) -> &'static str {
+        "idle"
+    }
+
+    fn on_enter(&mut self, _data: &mut MovableStateData) {
+        debug!("STARTED IDLE");
+    }
+
+    fn on_exit(&mut self, _data: &mut MovableStateData) {
+        debug!("STOPPED IDLE");
+    }
+}
+
+struct MoveState {
+    direction: MoveDirection,
+}
+
+impl State<Movable> for MoveState {
+    fn name(&self) -> &'static str {
+        "moving"
+    }
+
+    fn update(&mut self, data: &mut MovableStateData) {
+        info!("Move state update");
+        if let Some(direction) = MoveDirection::from_action(data.action) {
+            self.direction = direction;
+        }
+
+        let location = &mut *data.location;
+        let speed = data.transformation.speed;
+        let angle = data.transformation.facing.get_facing() + self.direction.relative_angle();
+        let calculated_speed = speed * data.delta.as_secs_f32();
+
+        let vx = calculated_speed * angle.cos();
+        let vy = calculated_speed * angle.sin();
+
+        let intended = Position::from_coord(
+            location.position.x() + vx as f64,
+            location.position.y() + vy as f64,
+        );
+        let new_position = match data.zone {
+            Some(zone) => zone.resolve_move(location.position, intended),
+            None => intended,
+        };
+
+        debug!("Position update to {:#?}", &new_position);
+        location.position = new_position;
+        if new_position.x() == intended.x() && new_position.y() == intended.y() {
+            data.state_delta.add(ObjectStateChange::Position(new_position));
+        } else {
+            data.state_delta.add(ObjectStateChange::Correction(new_position));
+        }
+    }
+
+    fn on_enter(&mut self, data: &mut MovableStateData) {
+        info!("STARTED MOVE");
+        self.direction = MoveDirection::from_action(data.action).unwrap_or(MoveDirection::Forward);
+        data.transformation.moving = true;
+        data.state_delta
+            .add(ObjectStateChange::Speed(data.transformation.speed));
+    }
+
+    fn on_exit(&mut self, data: &mut MovableStateData) {
+        info!("STOPPED MOVE");
+        data.transformation.moving = false;
+        data.state_delta.add(ObjectStateChange::Speed(0.0));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::behaviour::movement::{
+        movement_machine, Movable, MovableStateData, MOVEMENT_MACHINE,
+    };
+    use crate::common::quad_tree::QuadTree;
+    use crate::game::components::movement::{Location, Transformation};
+    use crate::game::components::state::StateMachineComponent;
+    use crate::game::location::pos::Position;
+    use crate::game::map::tile_map::TileMap;
+    use crate::game::map::zone::Zone;
+    use crate::net::data::PlayerInputAction;
+    use crate::net::packet::state_delta::ObjectStateChange;
+    use std::f32::consts::FRAC_PI_2;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn update(
+        state: &mut StateMachineComponent<Movable>,
+        transformation: &mut Transformation,
+        location: &mut Location,
+        action: Option<PlayerInputAction>,
+    ) -> Vec<ObjectStateChange> {
+        let mut data = MovableStateData::new(
+            transformation,
+            location,
+            None,
+            Duration::from_secs(1),
+            action,
+        );
+        state.update(&mut data);
+        data.state_delta.batch
+    }
+
+    #[test]
+    fn test_strafe_moves_sideways() {
+        let mut state = StateMachineComponent::new(vec![movement_machine()]);
+        let mut transformation = Transformation::new(1.0, FRAC_PI_2);
+        let mut location = Location {
+            position: Position::from_coord(0.0, 0.0),
+        };
+
+        update(
+            &mut state,
+            &mut transformation,
+            &mut location,
+            Some(PlayerInputAction::StrafeLeft),
+        );
+        update(&mut state, &mut transformation, &mut location, None);
+
+        assert!(location.position.x().abs() < 1e-5);
+        assert!((location.position.y() - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_turning_streams_facing() {
+        let mut state = StateMachineComponent::new(vec![movement_machine()]);
+        let mut transformation = Transformation::new(1.0, FRAC_PI_2);
+        let mut location = Location {
+            position: Position::from_coord(0.0, 0.0),
+        };
+
+        let changes = update(
+            &mut state,
+            &mut transformation,
+            &mut location,
+            Some(PlayerInputAction::TurnLeft),
+        );
+        match changes.as_slice() {
+            [ObjectStateChange::Facing(angle)] => assert!((angle - FRAC_PI_2).abs() < 1e-5),
+            _ => panic!("Facing change is not streamed"),
+        }
+
+        let changes = update(
+            &mut state,
+            &mut transformation,
+            &mut location,
+            Some(PlayerInputAction::StopTurn),
+        );
+        assert!(changes.is_empty());
+        assert!((transformation.facing.get_facing() - FRAC_PI_2).abs() < 1e-5);
+
+        update(
+            &mut state,
+            &mut transformation,
+            &mut location,
+            Some(PlayerInputAction::MoveBackward),
+        );
+        update(&mut state, &mut transformation, &mut location, None);
+        assert!((location.position.y() + 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_stop_move() {
+        let mut state = StateMachineComponent::new(vec![movement_machine()]);
+        let mut transformation = Transformation::new(1.0, FRAC_PI_2);
+        let mut location = Location {
+            position: Position::from_coord(0.0, 0.0),
+        };
+
+        update(
+            &mut state,
+            &mut transformation,
+            &mut location,
+            Some(PlayerInputAction::MoveForward),
+        );
+        update(&mut state, &mut transformation, &mut location, None);
+        assert!(transformation.moving);
+
+        let changes = update(
+            &mut state,
+            &mut transformation,
+            &mut location,
+            Some(PlayerInputAction::StopMove),
+        );
+        match changes.as_slice() {
+            [ObjectStateChange::Speed(speed)] => assert_eq!(*speed, 0.0),
+            _ => panic!("Stop is not streamed"),
+        }
+        assert!(!transformation.moving);
+        assert!((location.position.x() - 1.0).abs() < 1e-5);
+        assert_eq!(
+            state.machine(MOVEMENT_MACHINE).unwrap().current(),
+            "idle"
+        );
+    }
+
+    #[test]
+    fn test_blocked_move_is_corrected() {
+        let map = TileMap::parse("1\n.#", Position::new()).unwrap();
+        let zone = Zone::new(
+            "1".to_string(),
+            QuadTree::new(Position::new(), Position::from_coord(2.0, 1.0), 10, 1),
+            100.0,
+            Vec::new(),
+            Some(Arc::new(map)),
+        );
+        let mut state = StateMachineComponent::new(vec![movement_machine()]);
+        let mut transformation = Transformation::new(1.0, FRAC_PI_2);
+        let mut location = Location {
+            position: Position::from_coord(0.5, 0.5),
+        };
+
+        let mut changes = Vec::new();
+        for action in &[Some(PlayerInputAction::MoveForward), None] {
+            let mut data = MovableStateData::new(
+                &mut transformation,
+                &mut location,
+                Some(&zone),
+                Duration::from_secs(1),
+                *action,
+            );
+            state.update(&mut data);
+            changes = data.state_delta.batch;
+        }
+
+        match changes.as_slice() {
+            [ObjectStateChange::Correction(position)] => {
+                assert_eq!((position.x(), position.y()), (0.5, 0.5))
+            }
+            _ => panic!("Correction is not streamed"),
+        }
+        assert_eq!(location.position.x(), 0.5);
+    }
+}
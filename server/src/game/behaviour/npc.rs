@@ -0,0 +1,248 @@
+use crate::game::components::movement::{Location, Transformation};
+use crate::game::components::navigation::Navigation;
+use crate::game::components::state::{State, StateData, StateMachine};
+use crate::game::location::pos::Position;
+use crate::game::map::zone::Zone;
+use crate::net::packet::state_delta::{ObjectStateBatch, ObjectStateChange};
+use std::time::Duration;
+
+pub const NPC_MOVEMENT_MACHINE: &str = "npc_movement";
+
+/// Smallest change of the facing, that is streamed to the clients.
+const FACING_EPSILON: f32 = 1e-3;
+
+/// State machine of NPCs, that walk along the paths to their destination.
+pub struct Walker;
+
+impl StateData for Walker {
+    type Data<'a> = WalkerStateData<'a>;
+}
+
+pub struct WalkerStateData<'a> {
+    pub transformation: &'a mut Transformation,
+    pub location: &'a mut Location,
+    pub navigation: &'a mut Navigation,
+    /// Zone of the NPC, the paths are found in it.
+    pub zone: Option<&'a mut Zone>,
+    pub delta: Duration,
+    pub state_delta: ObjectStateBatch,
+}
+
+impl<'a> WalkerStateData<'a> {
+    pub fn new(
+        transformation: &'a mut Transformation,
+        location: &'a mut Location,
+        navigation: &'a mut Navigation,
+        zone: Option<&'a mut Zone>,
+        delta: Duration,
+    ) -> Self {
+        WalkerStateData {
+            transformation,
+            location,
+            navigation,
+            zone,
+            delta,
+            state_delta: ObjectStateBatch::new(),
+        }
+    }
+}
+
+pub fn npc_movement_machine() -> StateMachine<Walker> {
+    StateMachine::builder(NPC_MOVEMENT_MACHINE)
+        .state(IdleState)
+        .state(WalkState)
+        .initial("idle")
+        .transition("idle", "walking", |data| {
+            data.navigation.destination.is_some()
+        })
+        .transition("walking", "idle", |data| {
+            data.navigation.destination.is_none()
+        })
+        .build()
+}
+
+struct IdleState;
+
+impl State<Walker> for IdleState {
+    fn name(&self) -> &'static str {
+        "idle"
+    }
+}
+
+/// Follows the waypoints of the path with the speed of the NPC. The path is found when there
+/// is none, e.g. after the destination changed.
+struct WalkState;
+
+impl WalkState {
+    fn find_path(data: &mut WalkerStateData) -> bool {
+        let destination = match data.navigation.destination {
+            Some(destination) => destination,
+            None => return false,
+        };
+        let path = match &mut data.zone {
+            Some(zone) => zone.find_path(data.location.position, destination),
+            None => Some(vec![destination]),
+        };
+        match path {
+            Some(path) => {
+                data.navigation.path = path.into();
+                true
+            }
+            None => {
+                debug!("No path to {:?}", destination);
+                data.navigation.stop();
+                false
+            }
+        }
+    }
+}
+
+impl State<Walker> for WalkState {
+    fn name(&self) -> &'static str {
+        "walking"
+    }
+
+    fn update(&mut self, data: &mut WalkerStateData) {
+        if data.navigation.path.is_empty() && !WalkState::find_path(data) {
+            return;
+        }
+
+        let start = data.location.position;
+        let mut position = start;
+        let mut remaining = (data.transformation.speed * data.delta.as_secs_f32()) as f64;
+        while let Some(waypoint) = data.navigation.path.front().cloned() {
+            let distance = position.distance_to(&waypoint);
+            if distance > remaining {
+                let t = remaining / distance;
+                position = Position::from_coord(
+                    position.x() + (waypoint.x() - position.x()) * t,
+                    position.y() + (waypoint.y() - position.y()) * t,
+                );
+                break;
+            }
+            remaining -= distance;
+            position = waypoint;
+            data.navigation.path.pop_front();
+        }
+
+        let facing = ((position.y() - start.y()) as f32).atan2((position.x() - start.x()) as f32);
+        if start.distance_to(&position) > 0.0
+            && (facing - data.transformation.facing.get_facing()).abs() > FACING_EPSILON
+        {
+            data.transformation.facing.set(facing);
+            data.state_delta.add(ObjectStateChange::Facing(facing));
+        }
+        data.location.position = position;
+        data.state_delta.add(ObjectStateChange::Position(position));
+        if data.navigation.path.is_empty() {
+            data.navigation.destination.take();
+        }
+    }
+
+    fn on_enter(&mut self, data: &mut WalkerStateData) {
+        data.transformation.moving = true;
+        data.state_delta
+            .add(ObjectStateChange::Speed(data.transformation.speed));
+    }
+
+    fn on_exit(&mut self, data: &mut WalkerStateData) {
+        data.transformation.moving = false;
+        data.state_delta.add(ObjectStateChange::Speed(0.0));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::quad_tree::QuadTree;
+    use crate::game::behaviour::npc::{
+        npc_movement_machine, WalkerStateData, NPC_MOVEMENT_MACHINE,
+    };
+    use crate::game::components::movement::{Location, Transformation};
+    use crate::game::components::navigation::Navigation;
+    use crate::game::components::state::StateMachineComponent;
+    use crate::game::location::pos::Position;
+    use crate::game::map::tile_map::TileMap;
+    use crate::game::map::zone::Zone;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_walks_around_wall() {
+        let map = TileMap::parse("1\n...\n.#.\n...", Position::new()).unwrap();
+        let mut zone = Zone::new(
+            "1".to_string(),
+            QuadTree::new(Position::new(), Position::from_coord(3.0, 3.0), 10, 1),
+            100.0,
+            Vec::new(),
+            Some(Arc::new(map)),
+        );
+        let mut state = StateMachineComponent::new(vec![npc_movement_machine()]);
+        let mut transformation = Transformation::new(1.0, 0.0);
+        let mut location = Location {
+            position: Position::from_coord(1.5, 0.5),
+        };
+        let mut navigation = Navigation::new();
+        navigation.go_to(Position::from_coord(1.5, 2.5));
+
+        let mut ticks = 0;
+        let mut walked = false;
+        loop {
+            let mut data = WalkerStateData::new(
+                &mut transformation,
+                &mut location,
+                &mut navigation,
+                Some(&mut zone),
+                Duration::from_millis(500),
+            );
+            state.update(&mut data);
+            assert!(zone.is_walkable(location.position), "Walked into a wall");
+            walked |= transformation.moving;
+            if state.machine(NPC_MOVEMENT_MACHINE).unwrap().current() == "idle" && walked {
+                break;
+            }
+            ticks += 1;
+            assert!(ticks < 20, "Destination is not reached");
+        }
+
+        assert_eq!((location.position.x(), location.position.y()), (1.5, 2.5));
+        assert!(navigation.destination.is_none());
+        assert!(!transformation.moving);
+    }
+
+    #[test]
+    fn test_unreachable_destination() {
+        let map = TileMap::parse("1\n...\n###\n...", Position::new()).unwrap();
+        let mut zone = Zone::new(
+            "1".to_string(),
+            QuadTree::new(Position::new(), Position::from_coord(3.0, 3.0), 10, 1),
+            100.0,
+            Vec::new(),
+            Some(Arc::new(map)),
+        );
+        let mut state = StateMachineComponent::new(vec![npc_movement_machine()]);
+        let mut transformation = Transformation::new(1.0, 0.0);
+        let mut location = Location {
+            position: Position::from_coord(0.5, 0.5),
+        };
+        let mut navigation = Navigation::new();
+        navigation.go_to(Position::from_coord(0.5, 2.5));
+
+        for _ in 0..3 {
+            let mut data = WalkerStateData::new(
+                &mut transformation,
+                &mut location,
+                &mut navigation,
+                Some(&mut zone),
+                Duration::from_secs(1),
+            );
+            state.update(&mut data);
+        }
+
+        assert!(navigation.destination.is_none());
+        assert_eq!(location.position.y(), 0.5);
+        assert_eq!(
+            state.machine(NPC_MOVEMENT_MACHINE).unwrap().current(),
+            "idle"
+        );
+    }
+}
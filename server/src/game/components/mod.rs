@@ -4,3 +4,7 @@ pub mod state;
 pub mod connection;
 pub mod input_cache;
 pub mod obj;
+pub mod visibility;
+pub mod party;
+pub mod phase;
+pub mod navigation;
@@ -1,4 +1,4 @@
-use crate::game::location::facing::Facing;
+use crate::game::location::facing::{Facing, TurnDirection};
 use crate::game::location::pos::Position;
 use std::time::{Instant, Duration};
 
@@ -10,7 +10,32 @@ pub struct Location {
 #[derive(Debug)]
 pub struct Transformation {
     pub facing: Facing,
-    pub speed: f32
+    pub speed: f32,
+    /// Radians per second
+    pub turn_rate: f32,
+    pub turning: Option<TurnDirection>,
+    pub moving: bool,
+}
+
+impl Transformation {
+    pub fn new(speed: f32, turn_rate: f32) -> Self {
+        Transformation {
+            facing: Facing::new(),
+            speed,
+            turn_rate,
+            turning: None,
+            moving: false,
+        }
+    }
+
+    /// Speed the object is currently moving with.
+    pub fn current_speed(&self) -> f32 {
+        if self.moving {
+            self.speed
+        } else {
+            0.0
+        }
+    }
 }
 
 pub struct MovementComponent {
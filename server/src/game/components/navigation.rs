@@ -0,0 +1,29 @@
+use crate::game::location::pos::Position;
+use std::collections::VecDeque;
+
+/// Destination of an NPC and the waypoints leading to it.
+#[derive(Default)]
+pub struct Navigation {
+    pub destination: Option<Position>,
+    /// Remaining waypoints, found when the NPC starts walking to the destination.
+    pub path: VecDeque<Position>,
+}
+
+impl Navigation {
+    pub fn new() -> Self {
+        Navigation {
+            destination: None,
+            path: VecDeque::new(),
+        }
+    }
+
+    pub fn go_to(&mut self, destination: Position) {
+        self.destination.replace(destination);
+        self.path.clear();
+    }
+
+    pub fn stop(&mut self) {
+        self.destination.take();
+        self.path.clear();
+    }
+}
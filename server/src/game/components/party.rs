@@ -0,0 +1,15 @@
+pub type PartyId = u32;
+
+/// Members of the same party share the instances of the zones they enter.
+///
+/// Parties are not formed by the server yet, nothing outside the tests attaches this component,
+/// so every player gets an instance of its own.
+pub struct Party {
+    pub id: PartyId,
+}
+
+impl Party {
+    pub fn new(id: PartyId) -> Self {
+        Party { id }
+    }
+}
@@ -0,0 +1,18 @@
+/// Set of phases, one bit for every phase.
+pub type PhaseMask = u32;
+
+/// Phase everything is in, until it is moved to another one.
+pub const DEFAULT_PHASE: PhaseMask = 1;
+pub const ALL_PHASES: PhaseMask = PhaseMask::MAX;
+
+/// Phases an object exists in. Players only see it, when they are in one of them. Objects
+/// without a phase are seen in every phase.
+pub struct Phase {
+    pub mask: PhaseMask,
+}
+
+impl Phase {
+    pub fn new(mask: PhaseMask) -> Self {
+        Phase { mask }
+    }
+}
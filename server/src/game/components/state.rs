@@ -1,134 +1,466 @@
-use crate::game::components::movement::{Location, Transformation};
-use crate::game::location::pos::Position;
-use crate::net::data::PlayerInputAction;
-use crate::net::packet::state_delta::{ObjectStateBatch, ObjectStateChange};
-use std::sync::atomic::AtomicPtr;
-use std::time::Duration;
-
-type BoxedState<T> = Box<dyn State<T> + Sync + Send>;
-
-pub struct StateData {}
-
-pub struct MovableStateData {
-    pub transformation: AtomicPtr<Transformation>,
-    pub location: AtomicPtr<Location>,
-    pub delta: Duration,
-    pub action: Option<PlayerInputAction>,
-    pub state_delta: Option<ObjectStateBatch>,
+use std::collections::{HashMap, VecDeque};
+
+pub type BoxedState<T> = Box<dyn State<T> + Sync + Send>;
+type Guard<T> = Box<dyn Fn(&<T as StateData>::Data<'_>) -> bool + Sync + Send>;
+
+/// Number of transitions kept in the log of a state machine.
+const TRANSITION_LOG_SIZE: usize = 32;
+
+/// Kind of a state machine. The data of an update is borrowed from the components of the entity
+/// only for the duration of the update.
+pub trait StateData: 'static {
+    type Data<'a>;
 }
 
-impl<'a> MovableStateData {
-    pub fn new(
-        transformation: Option<&'a mut Transformation>,
-        location: Option<&'a mut Location>,
-        delta: Duration,
-        action: Option<PlayerInputAction>,
-    ) -> Self {
-        MovableStateData {
-            transformation: transformation.map_or(AtomicPtr::default(), |t| AtomicPtr::new(t)),
-            location: location.map_or(AtomicPtr::default(), |l| AtomicPtr::new(l)),
-            delta,
-            action,
-            state_delta: Some(ObjectStateBatch::new()),
-        }
-    }
+pub trait State<T: StateData> {
+    /// Unique name of the state within its machine, transitions refer to it.
+    fn name(&self) -> &'static str;
+
+    fn update(&mut self, _data: &mut T::Data<'_>) {}
+
+    fn on_enter(&mut self, _data: &mut T::Data<'_>) {}
+
+    fn on_exit(&mut self, _data: &mut T::Data<'_>) {}
 }
 
-pub trait State<T> {
-    fn update(&mut self, data: &mut T) -> Option<Box<dyn State<T> + Sync + Send>>;
-    fn on_start(&mut self);
-    fn on_stop(&mut self);
+struct StateNode<T: StateData> {
+    state: BoxedState<T>,
+    parent: Option<&'static str>,
 }
 
-struct IdleState;
+/// Moves the machine to another state, when its guard is satisfied. A transition of a state
+/// applies to all of its sub states.
+struct Transition<T: StateData> {
+    from: &'static str,
+    to: &'static str,
+    guard: Guard<T>,
+}
 
-impl State<MovableStateData> for IdleState {
-    fn update(&mut self, data: &mut MovableStateData) -> Option<BoxedState<MovableStateData>> {
-        match data.action {
-            Some(PlayerInputAction::MoveForward) => {
-                unsafe {
-                    let mut transformation = &mut (*(*data.transformation.get_mut()));
-                    data.state_delta.as_mut().unwrap().add(ObjectStateChange::Speed(transformation.speed));
-                }
-                Some(Box::new(MoveState {})) },
-            _ => None,
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TransitionRecord {
+    pub from: &'static str,
+    pub to: &'static str,
+}
+
+/// Hierarchical state machine. The active state and all of its ancestors are updated in every
+/// tick, the outermost first.
+///
+/// In an update the first satisfied transition of the active state, or the closest ancestor
+/// having one, is taken before the states are updated. States entered by the transition are only
+/// updated from the next tick on, their `on_enter` hook handles the tick of the transition.
+pub struct StateMachine<T: StateData> {
+    name: &'static str,
+    states: HashMap<&'static str, StateNode<T>>,
+    transitions: Vec<Transition<T>>,
+    current: &'static str,
+    started: bool,
+    log: VecDeque<TransitionRecord>,
+}
+
+impl<T: StateData> StateMachine<T> {
+    pub fn builder(name: &'static str) -> StateMachineBuilder<T> {
+        StateMachineBuilder {
+            name,
+            states: Vec::new(),
+            transitions: Vec::new(),
+            initial: None,
         }
     }
 
-    fn on_start(&mut self) {
-        debug!("STARTED IDLE");
+    pub fn name(&self) -> &'static str {
+        self.name
     }
 
-    fn on_stop(&mut self) {
-        debug!("STOPPED IDLE");
+    /// Name of the active innermost state.
+    pub fn current(&self) -> &'static str {
+        self.current
     }
-}
 
-struct MoveState;
+    /// Checks whether the state or one of its sub states is active.
+    pub fn is_in(&self, name: &str) -> bool {
+        self.path(self.current).contains(&name)
+    }
 
-impl State<MovableStateData> for MoveState {
-    fn update(&mut self, data: &mut MovableStateData) -> Option<BoxedState<MovableStateData>> {
-        info!("Move state update");
-        match data.action {
-            Some(PlayerInputAction::StopMove) => {
-                data.state_delta.as_mut().unwrap().add(ObjectStateChange::Speed(0.0));
-                return Some(Box::new(IdleState));
+    /// The latest transitions, the oldest first.
+    pub fn log(&self) -> impl Iterator<Item = &TransitionRecord> {
+        self.log.iter()
+    }
+
+    pub fn update(&mut self, data: &mut T::Data<'_>) {
+        if !self.started {
+            self.started = true;
+            for name in self.path(self.current) {
+                self.state_mut(name).on_enter(data);
             }
-            _ => (),
+        }
+
+        let entered = match self.triggered(data) {
+            Some(to) => self.transition(to, data),
+            None => Vec::new(),
         };
-        unsafe {
-            let mut location = &mut (*(*data.location.get_mut()));
-            let mut transformation = &mut (*(*data.transformation.get_mut()));
-            let speed = transformation.speed;
-            let angle = transformation.facing.get_facing();
-            let delta: Duration = data.delta;
-            let calculated_speed = speed * delta.as_secs() as f32;
+        for name in self.path(self.current) {
+            if !entered.contains(&name) {
+                self.state_mut(name).update(data);
+            }
+        }
+    }
+
+    /// Names of the states from the outermost ancestor to the given state.
+    fn path(&self, name: &'static str) -> Vec<&'static str> {
+        let mut path = vec![name];
+        let mut current = name;
+        while let Some(parent) = self.states[current].parent {
+            path.push(parent);
+            current = parent;
+        }
+        path.reverse();
+        path
+    }
 
-            let vx = calculated_speed * angle.cos();
-            let vy = calculated_speed * angle.sin();
+    fn state_mut(&mut self, name: &'static str) -> &mut BoxedState<T> {
+        &mut self.states.get_mut(name).unwrap().state
+    }
 
-            let new_position = Position::from_coord(
-                location.position.x() + vx as f64,
-                location.position.y() + vy as f64,
-            );
+    /// Finds the target of the first satisfied transition, the innermost states are checked
+    /// first.
+    fn triggered(&self, data: &T::Data<'_>) -> Option<&'static str> {
+        self.path(self.current).iter().rev().find_map(|name| {
+            self.transitions
+                .iter()
+                .find(|t| t.from == *name && (t.guard)(data))
+                .map(|t| t.to)
+        })
+    }
 
-            debug!("Position update to {:#?}", &new_position);
-            location.position = new_position;
-            data.state_delta.as_mut().unwrap().add(ObjectStateChange::Position(new_position));
+    /// Exits the active states up to the common ancestor, then enters the states down to the
+    /// target.
+    ///
+    /// # Returns
+    /// The names of the entered states.
+    fn transition(&mut self, to: &'static str, data: &mut T::Data<'_>) -> Vec<&'static str> {
+        let from_path = self.path(self.current);
+        let to_path = self.path(to);
+        let common = from_path
+            .iter()
+            .zip(to_path.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        for name in from_path[common..].iter().rev() {
+            self.state_mut(name).on_exit(data);
+        }
+        for name in &to_path[common..] {
+            self.state_mut(name).on_enter(data);
         }
 
-        None
+        debug!("State machine {}: {} -> {}", self.name, self.current, to);
+        if self.log.len() == TRANSITION_LOG_SIZE {
+            self.log.pop_front();
+        }
+        self.log.push_back(TransitionRecord {
+            from: self.current,
+            to,
+        });
+        self.current = to;
+        to_path[common..].to_vec()
     }
+}
 
-    fn on_start(&mut self) {
-        info!("STARTED MOVE");
+pub struct StateMachineBuilder<T: StateData> {
+    name: &'static str,
+    states: Vec<(BoxedState<T>, Option<&'static str>)>,
+    transitions: Vec<Transition<T>>,
+    initial: Option<&'static str>,
+}
+
+impl<T: StateData> StateMachineBuilder<T> {
+    pub fn state<S: State<T> + Sync + Send + 'static>(mut self, state: S) -> Self {
+        self.states.push((Box::new(state), None));
+        self
     }
 
-    fn on_stop(&mut self) {
-        info!("STOPPED MOVE");
+    pub fn sub_state<S: State<T> + Sync + Send + 'static>(
+        mut self,
+        parent: &'static str,
+        state: S,
+    ) -> Self {
+        self.states.push((Box::new(state), Some(parent)));
+        self
+    }
+
+    /// Transitions are checked in the order they are added.
+    pub fn transition<F>(mut self, from: &'static str, to: &'static str, guard: F) -> Self
+    where
+        F: Fn(&T::Data<'_>) -> bool + Sync + Send + 'static,
+    {
+        self.transitions.push(Transition {
+            from,
+            to,
+            guard: Box::new(guard),
+        });
+        self
+    }
+
+    /// The active state before the first update, defaults to the first added state. A state
+    /// having sub states starts in its first added sub state.
+    pub fn initial(mut self, name: &'static str) -> Self {
+        self.initial.replace(name);
+        self
+    }
+
+    /// # Panics
+    /// If a state name is used twice, a parent, transition or initial state refers to an
+    /// unknown state, or a state is its own ancestor.
+    pub fn build(self) -> StateMachine<T> {
+        let name = self.name;
+        let order: Vec<_> = self
+            .states
+            .iter()
+            .map(|(state, parent)| (state.name(), *parent))
+            .collect();
+        let mut states = HashMap::new();
+        for (state, parent) in self.states {
+            let state_name = state.name();
+            if states
+                .insert(state_name, StateNode { state, parent })
+                .is_some()
+            {
+                panic!("State machine {} has multiple {} states", name, state_name);
+            }
+        }
+        let known = |state: &str| {
+            if !states.contains_key(state) {
+                panic!("State machine {} has no {} state", name, state);
+            }
+        };
+        for node in states.values() {
+            node.parent.iter().for_each(|parent| known(parent));
+        }
+        for (&state_name, node) in &states {
+            let mut ancestors = 0;
+            let mut parent = node.parent;
+            while let Some(current) = parent {
+                ancestors += 1;
+                if current == state_name || ancestors > states.len() {
+                    panic!(
+                        "State machine {} has a parent cycle at {}",
+                        name, state_name
+                    );
+                }
+                parent = states[current].parent;
+            }
+        }
+        for transition in &self.transitions {
+            known(transition.from);
+            known(transition.to);
+        }
+        let mut initial = self
+            .initial
+            .or_else(|| order.first().map(|(state, _)| *state))
+            .unwrap_or_else(|| panic!("State machine {} has no states", name));
+        known(initial);
+        while let Some((sub_state, _)) = order.iter().find(|(_, p)| *p == Some(initial)) {
+            initial = sub_state;
+        }
+
+        StateMachine {
+            name,
+            states,
+            transitions: self.transitions,
+            current: initial,
+            started: false,
+            log: VecDeque::new(),
+        }
     }
 }
 
+/// Runs independent state machines side by side, e.g. movement and combat.
 pub struct StateMachineComponent<T>
 where
-    T: 'static,
+    T: StateData,
 {
-    state: Box<dyn State<T> + Send + Sync>,
+    machines: Vec<StateMachine<T>>,
 }
 
-impl StateMachineComponent<MovableStateData> {
-    pub fn new() -> Self {
-        StateMachineComponent {
-            state: Box::new(IdleState {}),
+impl<T: StateData> StateMachineComponent<T> {
+    pub fn new(machines: Vec<StateMachine<T>>) -> Self {
+        StateMachineComponent { machines }
+    }
+
+    pub fn machine(&self, name: &str) -> Option<&StateMachine<T>> {
+        self.machines.iter().find(|m| m.name() == name)
+    }
+
+    pub fn update(&mut self, data: &mut T::Data<'_>) {
+        for machine in &mut self.machines {
+            machine.update(data);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::components::state::{
+        State, StateData, StateMachine, StateMachineComponent, TransitionRecord,
+    };
+
+    struct Events;
+
+    impl StateData for Events {
+        type Data<'a> = &'a mut Vec<String>;
+    }
+
+    /// Records its hooks in the data.
+    struct Recorder(&'static str);
+
+    impl State<Events> for Recorder {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn update(&mut self, data: &mut &mut Vec<String>) {
+            data.push(format!("update {}", self.0));
+        }
+
+        fn on_enter(&mut self, data: &mut &mut Vec<String>) {
+            data.push(format!("enter {}", self.0));
+        }
+
+        fn on_exit(&mut self, data: &mut &mut Vec<String>) {
+            data.push(format!("exit {}", self.0));
         }
     }
 
-    pub fn update(&mut self, data: &mut MovableStateData) {
-        let new_state = self.state.update(data);
-        if let Some(s) = new_state {
-            self.state.on_stop();
-            self.state = s;
-            self.state.on_start();
+    fn requested(data: &&mut Vec<String>, event: &str) -> bool {
+        data.iter().any(|e| e == event)
+    }
+
+    fn machine() -> StateMachine<Events> {
+        StateMachine::builder("test")
+            .state(Recorder("alive"))
+            .sub_state("alive", Recorder("idle"))
+            .sub_state("alive", Recorder("walking"))
+            .state(Recorder("dead"))
+            .initial("idle")
+            .transition("idle", "walking", |data| requested(data, "walk"))
+            .transition("alive", "dead", |data| requested(data, "die"))
+            .build()
+    }
+
+    fn update(machine: &mut StateMachine<Events>, event: &str) -> Vec<String> {
+        let mut events = vec![event.to_string()];
+        machine.update(&mut &mut events);
+        events.split_off(1)
+    }
+
+    #[test]
+    fn test_hierarchical_transitions() {
+        let mut machine = machine();
+
+        assert_eq!(
+            update(&mut machine, ""),
+            vec!["enter alive", "enter idle", "update alive", "update idle"]
+        );
+        assert_eq!(
+            update(&mut machine, "walk"),
+            vec!["exit idle", "enter walking", "update alive"]
+        );
+        assert_eq!(
+            update(&mut machine, "walk"),
+            vec!["update alive", "update walking"]
+        );
+        assert!(machine.is_in("alive"));
+
+        // The transition of the parent state applies to its sub states as well
+        assert_eq!(
+            update(&mut machine, "die"),
+            vec!["exit walking", "exit alive", "enter dead"]
+        );
+        assert_eq!(machine.current(), "dead");
+        assert!(!machine.is_in("alive"));
+        assert_eq!(
+            machine.log().cloned().collect::<Vec<_>>(),
+            vec![
+                TransitionRecord {
+                    from: "idle",
+                    to: "walking"
+                },
+                TransitionRecord {
+                    from: "walking",
+                    to: "dead"
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parallel_machines() {
+        let combat = StateMachine::builder("combat")
+            .state(Recorder("peaceful"))
+            .state(Recorder("fighting"))
+            .transition("peaceful", "fighting", |data| requested(data, "attack"))
+            .build();
+        let mut component = StateMachineComponent::new(vec![machine(), combat]);
+
+        let mut events = vec!["walk".to_string(), "attack".to_string()];
+        component.update(&mut &mut events);
+        component.update(&mut &mut events);
+
+        assert_eq!(component.machine("test").unwrap().current(), "walking");
+        assert_eq!(component.machine("combat").unwrap().current(), "fighting");
+    }
+
+    #[test]
+    fn test_first_state_is_initial() {
+        for _ in 0..10 {
+            let mut machine = StateMachine::<Events>::builder("test")
+                .state(Recorder("a"))
+                .state(Recorder("b"))
+                .state(Recorder("c"))
+                .state(Recorder("d"))
+                .build();
+            assert_eq!(machine.current(), "a");
+            assert_eq!(update(&mut machine, ""), vec!["enter a", "update a"]);
         }
     }
+
+    #[test]
+    fn test_compound_initial_state() {
+        let mut machine = StateMachine::<Events>::builder("test")
+            .state(Recorder("alive"))
+            .sub_state("alive", Recorder("idle"))
+            .sub_state("idle", Recorder("resting"))
+            .sub_state("alive", Recorder("walking"))
+            .build();
+        assert_eq!(machine.current(), "resting");
+        assert_eq!(
+            update(&mut machine, ""),
+            vec![
+                "enter alive",
+                "enter idle",
+                "enter resting",
+                "update alive",
+                "update idle",
+                "update resting"
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "parent cycle")]
+    fn test_parent_cycle() {
+        StateMachine::<Events>::builder("test")
+            .state(Recorder("idle"))
+            .sub_state("b", Recorder("a"))
+            .sub_state("a", Recorder("b"))
+            .build();
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_unknown_transition_target() {
+        StateMachine::<Events>::builder("test")
+            .state(Recorder("idle"))
+            .transition("idle", "flying", |_| true)
+            .build();
+    }
 }
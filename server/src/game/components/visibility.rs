@@ -0,0 +1,43 @@
+use crate::common::obj_id::{GameObjectIdentifier, NetworkId};
+use crate::game::components::phase::{PhaseMask, DEFAULT_PHASE};
+use std::collections::HashMap;
+
+/// Objects, that the client of a player currently knows about.
+pub struct Visibility {
+    pub visible: HashMap<NetworkId, GameObjectIdentifier>,
+    /// Whether the client already got the world snapshot.
+    pub snapshot_sent: bool,
+    /// Phases the player sees, the objects of other phases are hidden from it.
+    pub phases: PhaseMask,
+}
+
+impl Visibility {
+    pub fn new() -> Self {
+        Visibility {
+            visible: HashMap::new(),
+            snapshot_sent: false,
+            phases: DEFAULT_PHASE,
+        }
+    }
+
+    /// Forgets everything, so that the next client gets a new world snapshot.
+    pub fn reset(&mut self) {
+        self.visible.clear();
+        self.snapshot_sent = false;
+    }
+
+    pub fn is_visible(&self, id: NetworkId) -> bool {
+        self.visible.contains_key(&id)
+    }
+
+    /// Checks whether the player shares a phase with an object in the given phases.
+    pub fn sees(&self, phases: PhaseMask) -> bool {
+        self.phases & phases != 0
+    }
+}
+
+impl Default for Visibility {
+    fn default() -> Self {
+        Visibility::new()
+    }
+}
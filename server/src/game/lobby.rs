@@ -1,16 +1,28 @@
+use crate::config::config::ServerConfig;
 use crate::game::components::input_cache::MovementInputCache;
 use crate::game::components::movement::{Location, Transformation};
-use crate::game::components::state::{MovableStateData, StateMachineComponent};
 use crate::game::location::facing::Facing;
 use crate::game::location::pos::Position;
 use crate::game::resource::frame::FrameResource;
+use crate::game::resource::heartbeat::HeartbeatSettings;
+use crate::game::resource::network::NetworkSettings;
+use crate::game::resource::network_id::NetworkIdRegistry;
+use crate::game::resource::session::SessionSettings;
 use crate::game::resource::user_manager::UserManagerStorage;
 use crate::game::resource::state_delta::StateDeltaCache;
 use crate::game::resource::zones::Zones;
+use crate::game::system::heartbeat::heartbeat_system;
+use crate::game::system::instance_teardown::instance_teardown_system;
+use crate::game::system::interest::interest_system;
 use crate::game::system::movement::movement_control_system;
-use crate::game::system::network_stream::network_stream;
+use crate::game::system::network_stream::network_stream_system;
+use crate::game::system::npc_movement::npc_movement_system;
 use crate::game::system::user_change::manage_users_system;
 use crate::game::system::user_input::user_input_system;
+use crate::game::system::zone_sync::zone_sync_system;
+use crate::game::system::zone_transfer::zone_transfer_system;
+use crate::game::timestep::FixedTimestep;
+use crate::user::session::SharedSessionRegistry;
 use crate::user::user::AuthenticatedUser;
 use crate::user::user_event::UserChangeEvent;
 use crossbeam_channel::Receiver;
@@ -18,68 +30,107 @@ use legion::{Resources, Schedule, SystemBuilder, World};
 use log::Level::Trace;
 use std::collections::{HashMap, VecDeque};
 use std::ops::Deref;
-use std::sync::atomic::AtomicPtr;
 use std::sync::{Arc, Mutex};
 use std::thread::sleep;
 use std::time::{Duration, Instant};
 
-const FPS: i32 = 1;
+/// Upper limit of ticks simulated in a burst, when the loop falls behind.
+const MAX_CATCH_UP_TICKS: u32 = 5;
 
 pub struct Lobby {
     world: World,
     dispatcher: Schedule,
     user_change: Receiver<UserChangeEvent>,
+    sessions: SharedSessionRegistry,
+    config: ServerConfig,
 }
 
 impl Lobby {
-    pub fn new(user_change_notifier: Receiver<UserChangeEvent>) -> Self {
+    pub fn new(
+        user_change_notifier: Receiver<UserChangeEvent>,
+        sessions: SharedSessionRegistry,
+        config: ServerConfig,
+    ) -> Self {
         Lobby {
             world: World::default(),
             dispatcher: Schedule::builder()
                 .add_system(manage_users_system())
                 .add_system(movement_control_system())
+                .add_system(npc_movement_system())
+                .add_system(zone_transfer_system())
+                .add_system(instance_teardown_system())
+                .add_system(zone_sync_system())
+                .add_system(interest_system())
                 .add_system(user_input_system())
+                .add_system(heartbeat_system())
+                .add_system(network_stream_system())
                 .build(),
             user_change: user_change_notifier,
+            sessions,
+            config,
         }
     }
 
-    pub fn start(&mut self) {
+    /// Runs the game loop in the world made of the zones.
+    pub fn start(&mut self, zones: Zones) {
         let mut resources = Resources::default();
-        let waiting_time = 1 / FPS;
-        resources.insert(FrameResource {
-            frame_delta: Duration::new(waiting_time as u64, 0),
-        });
+        let mut timestep = FixedTimestep::from_tick_rate(self.config.tick_rate, MAX_CATCH_UP_TICKS);
+        resources.insert(FrameResource::new(timestep.step()));
+        resources.insert(HeartbeatSettings::new(
+            self.config.heartbeat.timeout(),
+            self.config.heartbeat.ping_interval(),
+        ));
+        resources.insert(SessionSettings::new(
+            self.config.session.grace_period(),
+            self.sessions.clone(),
+        ));
+        resources.insert(NetworkSettings::new(self.config.network.max_batch_size));
         resources.insert(UserManagerStorage::new());
+        resources.insert(NetworkIdRegistry::new());
         resources.insert(StateDeltaCache::new());
-        resources.insert(Zones::default());
+        resources.insert(zones);
 
+        let mut last_update = Instant::now();
         loop {
-            let loop_start = Instant::now();
-            {
-                let user_manager = resources.get_mut::<UserManagerStorage>();
-                if let Some(mut user_manager) = user_manager {
-                    for change_event in self.user_change.try_iter() {
-                        match change_event {
-                            UserChangeEvent::NewUser(user) => {
-                                user_manager.new_users.push_front(user)
-                            }
-                            UserChangeEvent::DisconnectedUser(user) => {
-                                user_manager.disconnected_users.push_front(user)
-                            }
+            let now = Instant::now();
+            let ticks = timestep.advance(now - last_update);
+            last_update = now;
+
+            for _ in 0..ticks {
+                let tick_start = Instant::now();
+                self.tick(&mut resources);
+                let elapsed_tick_time = tick_start.elapsed();
+                if elapsed_tick_time > timestep.step() {
+                    warn!(
+                        "Tick overran its time budget of {:?} by {:?}",
+                        timestep.step(),
+                        elapsed_tick_time - timestep.step()
+                    );
+                }
+            }
+
+            sleep(timestep.until_next_tick());
+        }
+    }
+
+    /// Simulates a single fixed length tick.
+    fn tick(&mut self, resources: &mut Resources) {
+        {
+            let user_manager = resources.get_mut::<UserManagerStorage>();
+            if let Some(mut user_manager) = user_manager {
+                for change_event in self.user_change.try_iter() {
+                    match change_event {
+                        UserChangeEvent::NewUser(user) => user_manager.new_users.push_front(user),
+                        UserChangeEvent::DisconnectedUser(user) => {
+                            user_manager.disconnected_users.push_front(user)
                         }
                     }
                 }
             }
-            self.dispatcher.execute(&mut self.world, &mut resources);
-            network_stream(
-                AtomicPtr::new(&mut self.world),
-                AtomicPtr::new(&mut resources),
-            );
-            let elapsed_frame_time = Instant::now() - loop_start;
-            if elapsed_frame_time.as_secs() < waiting_time as u64 {
-                sleep(Duration::from_secs(waiting_time as u64) - elapsed_frame_time)
-            }
+        }
+        self.dispatcher.execute(&mut self.world, resources);
+        if let Some(mut frame) = resources.get_mut::<FrameResource>() {
+            frame.tick += 1;
         }
     }
 }
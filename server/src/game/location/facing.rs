@@ -2,6 +2,14 @@ use euclid::{Angle, UnknownUnit};
 
 const MAX_FACING: f32 = std::f32::consts::PI;
 
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TurnDirection {
+    Left,
+    Right,
+}
+
+/// Direction of an object as an angle in radians, counter-clockwise from the x axis.
+/// The angle is always kept in the (-PI, PI] range.
 #[derive(Copy, Clone, Debug)]
 pub struct Facing {
     angle: Angle<f32>,
@@ -15,12 +23,39 @@ impl Facing {
     }
 
     pub fn rad(rad: f32) -> Self {
-        Facing {
-            angle: Angle::radians(rad),
-        }
+        let mut facing = Facing::new();
+        facing.set(rad);
+        facing
     }
 
     pub fn get_facing(&self) -> f32 {
         self.angle.get()
     }
+
+    pub fn set(&mut self, rad: f32) {
+        self.angle = Angle::radians(rad).signed();
+    }
+
+    /// Rotates the facing by the given angle, positive angles turn left.
+    pub fn turn(&mut self, rad: f32) {
+        self.set(self.angle.get() + rad);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::location::facing::Facing;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn test_turn_wraps_around() {
+        let mut facing = Facing::rad(PI - 0.5);
+        facing.turn(1.0);
+        assert!((facing.get_facing() - (-PI + 0.5)).abs() < 1e-5);
+
+        facing.turn(-1.0);
+        assert!((facing.get_facing() - (PI - 0.5)).abs() < 1e-5);
+
+        assert!((Facing::rad(3.0 * PI).get_facing() - PI).abs() < 1e-5);
+    }
 }
@@ -59,6 +59,10 @@ impl Position {
     pub fn y(&self) -> f64 {
         self.internal.y
     }
+
+    pub fn distance_to(&self, other: &Position) -> f64 {
+        self.internal.distance_to(other.internal)
+    }
 }
 
 impl BBEncodable for Position {
@@ -68,7 +72,7 @@ impl BBEncodable for Position {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Area {
     internal: Box2D<f64, Unit>,
 }
@@ -97,7 +101,31 @@ impl Area {
         Position::from_coord(self.internal.max.x, self.internal.max.y)
     }
 
+    /// Square area around the center, that contains every position within the radius.
+    pub fn around(center: Position, radius: f64) -> Self {
+        Area::from_point(
+            (center.x() - radius, center.y() - radius),
+            (center.x() + radius, center.y() + radius),
+        )
+    }
+
     pub fn contains(&self, pos: Position) -> bool {
         self.internal.contains(pos.internal)
     }
+
+    /// Returns the closest position to the given one, that is within the area or on its edge.
+    pub fn clamp(&self, pos: Position) -> Position {
+        Position::from_coord(
+            pos.x().clamp(self.internal.min.x, self.internal.max.x),
+            pos.y().clamp(self.internal.min.y, self.internal.max.y),
+        )
+    }
+
+    /// Checks whether the two areas overlap. Areas that only share an edge intersect as well.
+    pub fn intersects(&self, other: &Area) -> bool {
+        self.internal.min.x <= other.internal.max.x
+            && self.internal.max.x >= other.internal.min.x
+            && self.internal.min.y <= other.internal.max.y
+            && self.internal.max.y >= other.internal.min.y
+    }
 }
@@ -0,0 +1,46 @@
+use crate::game::components::party::PartyId;
+use legion::Entity;
+use std::time::{Duration, Instant};
+
+pub type InstanceId = u32;
+
+/// Group of players sharing a copy of an instanced zone. Players without a party get a copy of
+/// their own, it is keyed by the entity, as network IDs are recycled.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum InstanceOwner {
+    Party(PartyId),
+    Player(Entity),
+}
+
+/// Copy of an instanced zone template.
+#[derive(Debug)]
+pub struct ZoneInstance {
+    pub id: InstanceId,
+    pub template: String,
+    pub owner: InstanceOwner,
+    empty_since: Option<Instant>,
+}
+
+impl ZoneInstance {
+    pub fn new(id: InstanceId, template: String, owner: InstanceOwner) -> Self {
+        ZoneInstance {
+            id,
+            template,
+            owner,
+            empty_since: None,
+        }
+    }
+
+    /// Tracks how long the instance has been empty.
+    ///
+    /// # Returns
+    /// Whether the instance has been empty for longer than the teardown time.
+    pub fn is_abandoned(&mut self, empty: bool, now: Instant, teardown: Duration) -> bool {
+        if !empty {
+            self.empty_since.take();
+            return false;
+        }
+        let empty_since = *self.empty_since.get_or_insert(now);
+        now.duration_since(empty_since) >= teardown
+    }
+}
@@ -1 +1,5 @@
-pub mod zone;
\ No newline at end of file
+pub mod zone;
+pub mod portal;
+pub mod instance;
+pub mod tile_map;
+pub mod pathfinding;
\ No newline at end of file
@@ -0,0 +1,395 @@
+use crate::game::location::pos::Position;
+use crate::game::map::tile_map::{Cell, TileMap};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+/// Crossings of a vertical and a horizontal tile edge closer than this are treated as a corner.
+const CORNER_EPSILON: f64 = 1e-9;
+const DEFAULT_CACHE_SIZE: usize = 256;
+
+const DIRECTIONS: [(isize, isize); 8] = [
+    (1, 0),
+    (0, 1),
+    (-1, 0),
+    (0, -1),
+    (1, 1),
+    (-1, 1),
+    (-1, -1),
+    (1, -1),
+];
+
+/// Finds the shortest path of walkable cells with A*, moving in eight directions. Diagonal moves
+/// may not cut the corner of a blocked cell. Ties are broken by the cell order, so the same
+/// query always gives the same path.
+///
+/// # Returns
+/// The cells from the start to the goal, both included, or None if the goal can not be reached.
+pub fn find_path(map: &TileMap, start: Cell, goal: Cell) -> Option<Vec<Cell>> {
+    if !map.is_walkable_cell(start) || !map.is_walkable_cell(goal) {
+        return None;
+    }
+    let mut open = BinaryHeap::new();
+    let mut closed = HashSet::new();
+    let mut costs: HashMap<Cell, u32> = HashMap::new();
+    let mut came_from: HashMap<Cell, Cell> = HashMap::new();
+    costs.insert(start, 0);
+    open.push(Reverse((
+        heuristic(start, goal),
+        heuristic(start, goal),
+        start,
+    )));
+
+    while let Some(Reverse((_, _, current))) = open.pop() {
+        if current == goal {
+            let mut path = vec![goal];
+            let mut cell = goal;
+            while let Some(previous) = came_from.get(&cell) {
+                path.push(*previous);
+                cell = *previous;
+            }
+            path.reverse();
+            return Some(path);
+        }
+        if !closed.insert(current) {
+            continue;
+        }
+
+        let cost = costs[&current];
+        for (next, step) in neighbours(map, current) {
+            let next_cost = cost + step;
+            if costs.get(&next).is_none_or(|c| next_cost < *c) {
+                costs.insert(next, next_cost);
+                came_from.insert(next, current);
+                let h = heuristic(next, goal);
+                open.push(Reverse((next_cost + h, h, next)));
+            }
+        }
+    }
+    None
+}
+
+/// Octile distance, the cost of the path without any obstacles.
+fn heuristic(from: Cell, to: Cell) -> u32 {
+    let dx = (from.0 as isize - to.0 as isize).unsigned_abs() as u32;
+    let dy = (from.1 as isize - to.1 as isize).unsigned_abs() as u32;
+    DIAGONAL_COST * dx.min(dy) + STRAIGHT_COST * (dx.max(dy) - dx.min(dy))
+}
+
+fn neighbours(map: &TileMap, cell: Cell) -> Vec<(Cell, u32)> {
+    let walkable =
+        |dx: isize, dy: isize| match (cell.0.checked_add_signed(dx), cell.1.checked_add_signed(dy))
+        {
+            (Some(x), Some(y)) if map.is_walkable_cell((x, y)) => Some((x, y)),
+            _ => None,
+        };
+    DIRECTIONS
+        .iter()
+        .filter_map(|(dx, dy)| {
+            let next = walkable(*dx, *dy)?;
+            if *dx != 0 && *dy != 0 {
+                walkable(*dx, 0)?;
+                walkable(0, *dy)?;
+                Some((next, DIAGONAL_COST))
+            } else {
+                Some((next, STRAIGHT_COST))
+            }
+        })
+        .collect()
+}
+
+/// Checks whether the straight line between the positions avoids the blocked tiles.
+///
+/// Every cell the line crosses is visited by walking the grid from edge to edge. When the line
+/// passes exactly through a corner, both cells beside the corner are checked as well.
+pub fn line_of_sight(map: &TileMap, from: Position, to: Position) -> bool {
+    let origin = map.origin();
+    let size = map.tile_size();
+    let (x0, y0) = (
+        (from.x() - origin.x()) / size,
+        (from.y() - origin.y()) / size,
+    );
+    let (x1, y1) = ((to.x() - origin.x()) / size, (to.y() - origin.y()) / size);
+    let (dx, dy) = (x1 - x0, y1 - y0);
+    let (step_x, step_y) = (if dx < 0.0 { -1 } else { 1 }, if dy < 0.0 { -1 } else { 1 });
+    // Line parameter of the next vertical and horizontal edge crossing, and between two edges
+    let edge = |start: f64, delta: f64| {
+        if delta > 0.0 {
+            (start.floor() + 1.0 - start) / delta
+        } else if delta < 0.0 {
+            (start - start.floor()) / -delta
+        } else {
+            f64::INFINITY
+        }
+    };
+    let (mut next_x, mut next_y) = (edge(x0, dx), edge(y0, dy));
+    let (delta_x, delta_y) = (1.0 / dx.abs(), 1.0 / dy.abs());
+
+    let blocked =
+        |x: i64, y: i64| x >= 0 && y >= 0 && map.is_blocked_cell((x as usize, y as usize));
+    let (mut x, mut y) = (x0.floor() as i64, y0.floor() as i64);
+    let end = (x1.floor() as i64, y1.floor() as i64);
+    let mut remaining = (end.0 - x).abs() + (end.1 - y).abs();
+    loop {
+        if blocked(x, y) {
+            return false;
+        }
+        if remaining <= 0 || (x, y) == end {
+            return true;
+        }
+        if (next_x - next_y).abs() < CORNER_EPSILON {
+            if blocked(x + step_x, y) || blocked(x, y + step_y) {
+                return false;
+            }
+            x += step_x;
+            y += step_y;
+            next_x += delta_x;
+            next_y += delta_y;
+            remaining -= 2;
+        } else if next_x < next_y {
+            x += step_x;
+            next_x += delta_x;
+            remaining -= 1;
+        } else {
+            y += step_y;
+            next_y += delta_y;
+            remaining -= 1;
+        }
+    }
+}
+
+/// Removes the waypoints, that can be skipped by walking straight to a later one. The first and
+/// last positions are kept.
+pub fn smooth(map: &TileMap, points: &[Position]) -> Vec<Position> {
+    let (first, last) = match (points.first(), points.last()) {
+        (Some(first), Some(last)) if points.len() > 2 => (*first, *last),
+        _ => return points.to_vec(),
+    };
+    let mut smoothed = vec![first];
+    let mut anchor = first;
+    for window in points.windows(2).skip(1) {
+        if !line_of_sight(map, anchor, window[1]) {
+            anchor = window[0];
+            smoothed.push(anchor);
+        }
+    }
+    smoothed.push(last);
+    smoothed
+}
+
+/// Paths between cells found in a zone, the oldest ones are dropped first when it is full.
+pub struct PathCache {
+    paths: HashMap<(Cell, Cell), Option<Vec<Cell>>>,
+    order: VecDeque<(Cell, Cell)>,
+    capacity: usize,
+}
+
+impl PathCache {
+    pub fn new(capacity: usize) -> Self {
+        PathCache {
+            paths: HashMap::new(),
+            order: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// Finds the smoothed path between two positions on the map.
+    ///
+    /// # Returns
+    /// The waypoints after the start position, ending with the target, or None if it can not be
+    /// reached.
+    pub fn find_path(
+        &mut self,
+        map: &TileMap,
+        from: Position,
+        to: Position,
+    ) -> Option<Vec<Position>> {
+        if line_of_sight(map, from, to) {
+            return Some(vec![to]);
+        }
+        let key = (map.cell_of(from)?, map.cell_of(to)?);
+        if !self.paths.contains_key(&key) {
+            if self.order.len() >= self.capacity {
+                if let Some(oldest) = self.order.pop_front() {
+                    self.paths.remove(&oldest);
+                }
+            }
+            self.paths.insert(key, find_path(map, key.0, key.1));
+            self.order.push_back(key);
+        }
+        let cells = self.paths[&key].as_ref()?;
+
+        let mut points = vec![from];
+        points.extend(
+            cells
+                .iter()
+                .skip(1)
+                .take(cells.len().saturating_sub(2))
+                .map(|cell| map.center_of(*cell)),
+        );
+        points.push(to);
+        Some(smooth(map, &points).split_off(1))
+    }
+}
+
+impl Default for PathCache {
+    fn default() -> Self {
+        PathCache::new(DEFAULT_CACHE_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::location::pos::Position;
+    use crate::game::map::pathfinding::{find_path, line_of_sight, smooth, PathCache};
+    use crate::game::map::tile_map::TileMap;
+
+    fn parse(rows: &str) -> TileMap {
+        TileMap::parse(&format!("1\n{}", rows), Position::new()).unwrap()
+    }
+
+    fn pos(x: f64, y: f64) -> Position {
+        Position::from_coord(x, y)
+    }
+
+    fn coords(path: &[Position]) -> Vec<(f64, f64)> {
+        path.iter().map(|p| (p.x(), p.y())).collect()
+    }
+
+    #[test]
+    fn test_path_around_wall() {
+        let map = parse(
+            "....\n\
+             .##.\n\
+             ###.",
+        );
+
+        assert_eq!(
+            find_path(&map, (0, 1), (3, 1)),
+            Some(vec![(0, 1), (0, 0), (1, 0), (2, 0), (3, 0), (3, 1)])
+        );
+        assert_eq!(find_path(&map, (0, 0), (0, 0)), Some(vec![(0, 0)]));
+        assert_eq!(find_path(&map, (0, 0), (1, 1)), None);
+    }
+
+    #[test]
+    fn test_no_corner_cutting() {
+        let map = parse(
+            ".#\n\
+             ..",
+        );
+        assert_eq!(
+            find_path(&map, (0, 0), (1, 1)),
+            Some(vec![(0, 0), (0, 1), (1, 1)])
+        );
+
+        let closed = parse(
+            ".#\n\
+             #.",
+        );
+        assert_eq!(find_path(&closed, (0, 0), (1, 1)), None);
+    }
+
+    #[test]
+    fn test_unreachable() {
+        let map = parse(
+            "..#..\n\
+             ..#..\n\
+             ..#..",
+        );
+        assert_eq!(find_path(&map, (0, 0), (4, 2)), None);
+    }
+
+    #[test]
+    fn test_smooth() {
+        let map = parse(
+            ".....\n\
+             .....\n\
+             ..#..",
+        );
+        assert!(line_of_sight(&map, pos(0.5, 0.5), pos(0.5, 2.5)));
+        assert!(!line_of_sight(&map, pos(0.5, 2.5), pos(4.5, 2.5)));
+
+        let path = [
+            pos(0.5, 0.5),
+            pos(1.5, 0.5),
+            pos(1.5, 1.5),
+            pos(2.5, 1.5),
+            pos(3.5, 1.5),
+            pos(3.5, 2.5),
+            pos(4.5, 2.5),
+        ];
+        assert_eq!(
+            coords(&smooth(&map, &path)),
+            vec![(0.5, 0.5), (3.5, 1.5), (4.5, 2.5)]
+        );
+    }
+
+    #[test]
+    fn test_line_clipping_corner() {
+        let map = parse(
+            "....\n\
+             .#..\n\
+             ....",
+        );
+        // Crosses the blocked cell only between x = 1.92 and 2, below y = 1.08
+        assert!(!line_of_sight(&map, pos(1.5, 0.58), pos(3.5, 2.58)));
+        assert!(!line_of_sight(&map, pos(3.5, 2.58), pos(1.5, 0.58)));
+        // Touches the corner of the blocked cell
+        assert!(!line_of_sight(&map, pos(1.5, 0.5), pos(2.5, 1.5)));
+        // Passes below the corner
+        assert!(line_of_sight(&map, pos(1.5, 0.48), pos(3.5, 2.48)));
+
+        let path = [pos(1.5, 0.58), pos(2.5, 0.5), pos(3.5, 2.58)];
+        assert_eq!(
+            coords(&smooth(&map, &path)),
+            vec![(1.5, 0.58), (2.5, 0.5), (3.5, 2.58)]
+        );
+    }
+
+    #[test]
+    fn test_cached_path() {
+        let map = parse(
+            ".....\n\
+             .###.\n\
+             .....",
+        );
+        let mut cache = PathCache::new(1);
+
+        assert_eq!(
+            cache
+                .find_path(&map, pos(0.5, 0.5), pos(4.5, 0.5))
+                .map(|p| coords(&p)),
+            Some(vec![(4.5, 0.5)])
+        );
+        assert!(cache.is_empty());
+
+        let path = cache.find_path(&map, pos(2.5, 0.5), pos(2.5, 2.5)).unwrap();
+        assert_eq!(cache.len(), 1);
+        assert_eq!(coords(&path), vec![(0.5, 0.5), (0.5, 2.5), (2.5, 2.5)]);
+        for p in &path {
+            assert!(!map.is_blocked(*p));
+        }
+        assert_eq!(
+            cache
+                .find_path(&map, pos(2.4, 0.6), pos(2.5, 2.5))
+                .map(|p| coords(&p)),
+            Some(vec![(0.5, 0.5), (0.5, 2.5), (2.5, 2.5)])
+        );
+        assert_eq!(cache.len(), 1);
+
+        assert!(cache
+            .find_path(&map, pos(0.5, 0.5), pos(2.5, 1.5))
+            .is_none());
+        assert_eq!(cache.len(), 1);
+    }
+}
@@ -0,0 +1,20 @@
+use crate::game::location::pos::{Area, Position};
+
+/// Area of a zone, that moves the objects walking into it to another zone.
+#[derive(Clone, Debug)]
+pub struct Portal {
+    pub area: Area,
+    pub destination: String,
+    /// Position in the destination zone, where the objects arrive.
+    pub spawn: Position,
+}
+
+impl Portal {
+    pub fn new(area: Area, destination: String, spawn: Position) -> Self {
+        Portal {
+            area,
+            destination,
+            spawn,
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use crate::error::error::Error;
+use crate::game::location::pos::Position;
+use std::fs;
+use std::path::Path;
+
+const COMMENT: char = ';';
+const BLOCKED: char = '#';
+const GROUND: char = '.';
+
+/// Column and row of a tile.
+pub type Cell = (usize, usize);
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Tile {
+    Blocked,
+    /// Walkable tile at the given height.
+    Ground(u8),
+}
+
+/// Grid of square tiles covering a zone from its minimum corner.
+///
+/// The map file is plain text. Lines starting with `;` are comments, the first remaining line is
+/// the size of a tile, followed by one line for every row of tiles, the row at the lowest y
+/// first. A tile is either `#` for blocked, `.` for ground or a digit for ground at that height.
+#[derive(Debug)]
+pub struct TileMap {
+    origin: Position,
+    tile_size: f64,
+    width: usize,
+    height: usize,
+    tiles: Vec<Tile>,
+}
+
+impl TileMap {
+    pub fn load(path: &Path, origin: Position) -> Result<Self, Error> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| Error::MapError(format!("Unable to read {}: {}", path.display(), e)))?;
+        TileMap::parse(&content, origin)
+            .map_err(|e| Error::MapError(format!("{}: {}", path.display(), e)))
+    }
+
+    pub fn parse(content: &str, origin: Position) -> Result<Self, Error> {
+        let mut lines = content
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with(COMMENT));
+        let tile_size = lines
+            .next()
+            .and_then(|l| l.parse::<f64>().ok())
+            .filter(|size| size.is_finite() && *size > 0.0)
+            .ok_or_else(|| Error::MapError("Missing or invalid tile size".to_string()))?;
+
+        let mut tiles = Vec::new();
+        let mut width = None;
+        let mut height = 0;
+        for line in lines {
+            let row = line
+                .chars()
+                .map(|c| match c {
+                    BLOCKED => Ok(Tile::Blocked),
+                    GROUND => Ok(Tile::Ground(0)),
+                    _ => c
+                        .to_digit(10)
+                        .map(|h| Tile::Ground(h as u8))
+                        .ok_or_else(|| Error::MapError(format!("Unknown tile {}", c))),
+                })
+                .collect::<Result<Vec<Tile>, Error>>()?;
+            if *width.get_or_insert(row.len()) != row.len() {
+                return Err(Error::MapError(format!(
+                    "Row {} has a different width",
+                    height
+                )));
+            }
+            tiles.extend(row);
+            height += 1;
+        }
+
+        Ok(TileMap {
+            origin,
+            tile_size,
+            width: width.unwrap_or(0),
+            height,
+            tiles,
+        })
+    }
+
+    pub fn tile_size(&self) -> f64 {
+        self.tile_size
+    }
+
+    /// Corner of the map with the lowest coordinates.
+    pub fn origin(&self) -> Position {
+        self.origin
+    }
+
+    /// Returns the cell under the position, or None if it is not covered by the map.
+    pub fn cell_of(&self, position: Position) -> Option<Cell> {
+        let x = ((position.x() - self.origin.x()) / self.tile_size).floor();
+        let y = ((position.y() - self.origin.y()) / self.tile_size).floor();
+        if x < 0.0 || y < 0.0 || x >= self.width as f64 || y >= self.height as f64 {
+            return None;
+        }
+        Some((x as usize, y as usize))
+    }
+
+    pub fn center_of(&self, cell: Cell) -> Position {
+        Position::from_coord(
+            self.origin.x() + (cell.0 as f64 + 0.5) * self.tile_size,
+            self.origin.y() + (cell.1 as f64 + 0.5) * self.tile_size,
+        )
+    }
+
+    /// Cells outside the map are not walkable.
+    pub fn is_walkable_cell(&self, cell: Cell) -> bool {
+        cell.0 < self.width
+            && cell.1 < self.height
+            && self.tiles[cell.1 * self.width + cell.0] != Tile::Blocked
+    }
+
+    /// Cells outside the map are not blocked.
+    pub fn is_blocked_cell(&self, cell: Cell) -> bool {
+        cell.0 < self.width
+            && cell.1 < self.height
+            && self.tiles[cell.1 * self.width + cell.0] == Tile::Blocked
+    }
+
+    /// Returns the tile under the position, or None if it is not covered by the map.
+    pub fn tile_at(&self, position: Position) -> Option<Tile> {
+        let (x, y) = self.cell_of(position)?;
+        Some(self.tiles[y * self.width + x])
+    }
+
+    /// Positions outside the map are not blocked.
+    pub fn is_blocked(&self, position: Position) -> bool {
+        self.tile_at(position) == Some(Tile::Blocked)
+    }
+
+    /// Height of the ground under the position, None if it is blocked or outside the map.
+    pub fn height_at(&self, position: Position) -> Option<u8> {
+        match self.tile_at(position)? {
+            Tile::Ground(height) => Some(height),
+            Tile::Blocked => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::location::pos::Position;
+    use crate::game::map::tile_map::{Tile, TileMap};
+
+    const MAP: &str = "
+        ; Test map
+        10
+        ..#
+        .2#
+        ";
+
+    #[test]
+    fn test_parse() {
+        let map = TileMap::parse(MAP, Position::from_coord(100.0, 100.0)).expect("Valid map");
+
+        assert_eq!(
+            map.tile_at(Position::from_coord(105.0, 105.0)),
+            Some(Tile::Ground(0))
+        );
+        assert!(map.is_blocked(Position::from_coord(125.0, 105.0)));
+        assert!(map.is_blocked(Position::from_coord(129.9, 119.9)));
+        assert_eq!(map.height_at(Position::from_coord(115.0, 115.0)), Some(2));
+        assert_eq!(map.tile_at(Position::from_coord(95.0, 105.0)), None);
+        assert_eq!(map.tile_at(Position::from_coord(130.0, 105.0)), None);
+        assert!(!map.is_blocked(Position::from_coord(105.0, 125.0)));
+    }
+
+    #[test]
+    fn test_invalid_map() {
+        let origin = Position::new();
+        assert!(TileMap::parse("..#\n...", origin).is_err());
+        assert!(TileMap::parse("10\n..#\n..", origin).is_err());
+        assert!(TileMap::parse("10\n..x", origin).is_err());
+        assert!(TileMap::parse("-1\n...", origin).is_err());
+    }
+}
@@ -1,17 +1,80 @@
+use crate::common::obj_id::NetworkId;
 use crate::common::quad_tree::QuadTree;
-use crate::game::location::pos::{Area, LocatableGameObject, Position};
-use legion::Entity;
-use std::borrow::Borrow;
-use std::collections::HashMap;
+use crate::game::location::pos::{LocatableGameObject, Position, Positionable};
+use crate::game::map::pathfinding::PathCache;
+use crate::game::map::portal::Portal;
+use crate::game::map::tile_map::TileMap;
+use std::sync::Arc;
 
 pub struct Zone {
     id: String,
-    pub grid: QuadTree<LocatableGameObject>,
+    pub grid: QuadTree<NetworkId, LocatableGameObject>,
+    view_distance: f64,
+    portals: Vec<Portal>,
+    /// Shared by the instances of the same template.
+    map: Option<Arc<TileMap>>,
+    paths: PathCache,
 }
 
 impl Zone {
-    pub fn new(id: String, grid: QuadTree<LocatableGameObject>) -> Self {
-        Zone { id, grid }
+    pub fn new(
+        id: String,
+        grid: QuadTree<NetworkId, LocatableGameObject>,
+        view_distance: f64,
+        portals: Vec<Portal>,
+        map: Option<Arc<TileMap>>,
+    ) -> Self {
+        Zone {
+            id,
+            grid,
+            view_distance,
+            portals,
+            map,
+            paths: PathCache::default(),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns the portal, that the position is in.
+    pub fn portal_at(&self, position: Position) -> Option<&Portal> {
+        self.portals.iter().find(|p| p.area.contains(position))
+    }
+
+    pub fn is_walkable(&self, position: Position) -> bool {
+        self.map.as_ref().is_none_or(|map| !map.is_blocked(position))
+    }
+
+    /// Resolves the move of an object to the target position. The target is clamped to the
+    /// bounds of the zone. A move into a blocked tile slides along the blocking axis, or is refused
+    /// if neither axis is free.
+    ///
+    /// # Returns
+    /// The position, where the object ends up.
+    pub fn resolve_move(&self, from: Position, to: Position) -> Position {
+        let to = self.grid.bounds().clamp(to);
+        let candidates = [
+            to,
+            Position::from_coord(to.x(), from.y()),
+            Position::from_coord(from.x(), to.y()),
+        ];
+        candidates
+            .iter()
+            .find(|p| self.is_walkable(**p))
+            .cloned()
+            .unwrap_or(from)
+    }
+
+    /// Finds the waypoints leading to the target, which is clamped to the bounds of the zone.
+    /// Without a tile map the target is reached in a straight line.
+    pub fn find_path(&mut self, from: Position, to: Position) -> Option<Vec<Position>> {
+        let to = self.grid.bounds().clamp(to);
+        match &self.map {
+            Some(map) => self.paths.find_path(map, from, to),
+            None => Some(vec![to]),
+        }
     }
 }
 
@@ -25,15 +88,56 @@ impl Default for Zone {
                 100,
                 4,
             ),
+            view_distance: 100.0,
+            portals: Vec::new(),
+            map: None,
+            paths: PathCache::default(),
         }
     }
 }
 
 impl Zone {
-    pub fn get_neighbors_of(
-        &mut self,
-        id: String,
-    ) -> Option<&mut HashMap<String, LocatableGameObject>> {
-        self.grid.find_node_of_value(&id).map(|n| n.get_values())
+    /// Returns every object within the view distance of the given object, including itself.
+    pub fn get_neighbors_of(&self, id: NetworkId) -> Option<Vec<&LocatableGameObject>> {
+        let center = self.grid.get(&id)?.position();
+        Some(self.grid.query_radius(center, self.view_distance))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::quad_tree::QuadTree;
+    use crate::game::location::pos::Position;
+    use crate::game::map::tile_map::TileMap;
+    use crate::game::map::zone::Zone;
+    use std::sync::Arc;
+
+    fn resolve(zone: &Zone, from: (f64, f64), to: (f64, f64)) -> (f64, f64) {
+        let position = zone.resolve_move(
+            Position::from_coord(from.0, from.1),
+            Position::from_coord(to.0, to.1),
+        );
+        (position.x(), position.y())
+    }
+
+    #[test]
+    fn test_resolve_move() {
+        let map = TileMap::parse("10\n...\n.#.\n...", Position::new()).unwrap();
+        let zone = Zone::new(
+            "1".to_string(),
+            QuadTree::new(Position::new(), Position::from_coord(30.0, 30.0), 10, 2),
+            100.0,
+            Vec::new(),
+            Some(Arc::new(map)),
+        );
+
+        assert_eq!(resolve(&zone, (5.0, 5.0), (8.0, 6.0)), (8.0, 6.0));
+        assert_eq!(resolve(&zone, (25.0, 25.0), (40.0, 27.0)), (30.0, 27.0));
+        // Sliding along the blocked tile
+        assert_eq!(resolve(&zone, (5.0, 15.0), (12.0, 17.0)), (5.0, 17.0));
+        assert_eq!(resolve(&zone, (15.0, 5.0), (17.0, 12.0)), (17.0, 5.0));
+        assert_eq!(resolve(&zone, (5.0, 5.0), (15.0, 15.0)), (15.0, 5.0));
+        // Walking straight into the blocked tile
+        assert_eq!(resolve(&zone, (15.0, 5.0), (15.0, 12.0)), (15.0, 5.0));
     }
 }
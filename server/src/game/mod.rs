@@ -1,6 +1,8 @@
 pub mod location;
 pub mod map;
 pub mod lobby;
+pub mod behaviour;
 pub mod components;
 pub mod system;
 pub mod resource;
+pub mod timestep;
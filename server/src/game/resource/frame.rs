@@ -1,13 +1,26 @@
 use std::time::{Duration, Instant};
 
 pub struct FrameResource {
+    /// Simulated time of a single tick.
     pub frame_delta: Duration,
+    /// Number of the current tick, monotonically increasing from 0.
+    pub tick: u64,
+}
+
+impl FrameResource {
+    pub fn new(frame_delta: Duration) -> Self {
+        FrameResource {
+            frame_delta,
+            tick: 0,
+        }
+    }
 }
 
 impl Default for FrameResource {
     fn default() -> Self {
         FrameResource {
-            frame_delta: Duration::new(0, 0)
+            frame_delta: Duration::new(0, 0),
+            tick: 0,
         }
     }
 }
@@ -0,0 +1,29 @@
+use std::time::Duration;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_PING_INTERVAL_SECS: u64 = 5;
+
+/// Controls how often clients are pinged and how long they may stay silent before they are
+/// considered disconnected.
+pub struct HeartbeatSettings {
+    pub timeout: Duration,
+    pub ping_interval: Duration,
+}
+
+impl HeartbeatSettings {
+    pub fn new(timeout: Duration, ping_interval: Duration) -> Self {
+        HeartbeatSettings {
+            timeout,
+            ping_interval,
+        }
+    }
+}
+
+impl Default for HeartbeatSettings {
+    fn default() -> Self {
+        HeartbeatSettings {
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECS),
+            ping_interval: Duration::from_secs(DEFAULT_PING_INTERVAL_SECS),
+        }
+    }
+}
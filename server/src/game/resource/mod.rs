@@ -1,4 +1,8 @@
 pub mod frame;
+pub mod heartbeat;
+pub mod network;
+pub mod network_id;
+pub mod session;
 pub mod user_manager;
 pub mod state_delta;
-pub mod zones;
+pub mod zones;
\ No newline at end of file
@@ -0,0 +1,11 @@
+/// Controls how the packets of a tick are sent to the clients.
+pub struct NetworkSettings {
+    /// Maximal size of a batched frame in bytes.
+    pub max_batch_size: usize,
+}
+
+impl NetworkSettings {
+    pub fn new(max_batch_size: usize) -> Self {
+        NetworkSettings { max_batch_size }
+    }
+}
@@ -0,0 +1,50 @@
+use crate::common::obj_id::NetworkId;
+use std::collections::VecDeque;
+
+/// Allocates the network IDs of game objects. Released IDs are recycled in the order they were
+/// released, so that an ID is reused as late as possible.
+pub struct NetworkIdRegistry {
+    next: NetworkId,
+    released: VecDeque<NetworkId>,
+}
+
+impl NetworkIdRegistry {
+    pub fn new() -> Self {
+        NetworkIdRegistry {
+            next: 1,
+            released: VecDeque::new(),
+        }
+    }
+
+    pub fn allocate(&mut self) -> NetworkId {
+        if let Some(id) = self.released.pop_front() {
+            return id;
+        }
+        let id = self.next;
+        self.next += 1;
+        id
+    }
+
+    pub fn release(&mut self, id: NetworkId) {
+        self.released.push_back(id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::resource::network_id::NetworkIdRegistry;
+
+    #[test]
+    fn test_recycle_released_ids() {
+        let mut registry = NetworkIdRegistry::new();
+        let first = registry.allocate();
+        let second = registry.allocate();
+        assert_ne!(first, second);
+
+        registry.release(second);
+        registry.release(first);
+        assert_eq!(registry.allocate(), second);
+        assert_eq!(registry.allocate(), first);
+        assert_ne!(registry.allocate(), first);
+    }
+}
@@ -0,0 +1,18 @@
+use crate::user::session::SharedSessionRegistry;
+use std::time::Duration;
+
+/// Controls how long the entity of a disconnected player is kept in the world. During the grace
+/// period the player can reconnect with its session token and take over the same entity.
+pub struct SessionSettings {
+    pub grace_period: Duration,
+    pub registry: SharedSessionRegistry,
+}
+
+impl SessionSettings {
+    pub fn new(grace_period: Duration, registry: SharedSessionRegistry) -> Self {
+        SessionSettings {
+            grace_period,
+            registry,
+        }
+    }
+}
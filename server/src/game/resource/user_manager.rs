@@ -4,12 +4,16 @@ use itertools::Itertools;
 use std::collections::{HashMap, VecDeque};
 use std::fmt::{Display, Formatter};
 use std::net::SocketAddr;
+use std::time::{Duration, Instant};
 
 #[derive(Default)]
 pub struct UserManagerStorage {
     pub new_users: VecDeque<AuthenticatedUser>,
     pub disconnected_users: VecDeque<SocketAddr>,
     pub socket_to_id: HashMap<SocketAddr, GameObjectIdentifier>,
+    pub name_to_id: HashMap<String, GameObjectIdentifier>,
+    /// Players without a connection and the time they were disconnected.
+    pub link_dead: HashMap<String, Instant>,
 }
 
 impl UserManagerStorage {
@@ -18,6 +22,22 @@ impl UserManagerStorage {
             new_users: VecDeque::new(),
             disconnected_users: VecDeque::new(),
             socket_to_id: HashMap::new(),
+            name_to_id: HashMap::new(),
+            link_dead: HashMap::new(),
         }
     }
+
+    /// Removes and returns the link dead players, whose grace period is over.
+    pub fn take_expired(&mut self, now: Instant, grace_period: Duration) -> Vec<(String, Instant)> {
+        let expired: Vec<(String, Instant)> = self
+            .link_dead
+            .iter()
+            .filter(|(_, since)| now.duration_since(**since) >= grace_period)
+            .map(|(name, since)| (name.clone(), *since))
+            .collect();
+        for (name, _) in &expired {
+            self.link_dead.remove(name);
+        }
+        expired
+    }
 }
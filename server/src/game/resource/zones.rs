@@ -1,10 +1,190 @@
 use crate::common::quad_tree::QuadTree;
-use crate::game::location::pos::{LocatableGameObject, Position};
+use crate::config::config::{QuadTreeConfig, ZoneConfig};
+use crate::error::error::Error;
+use crate::game::location::pos::Position;
+use crate::game::map::instance::{InstanceId, InstanceOwner, ZoneInstance};
+use crate::game::map::portal::Portal;
+use crate::game::map::tile_map::TileMap;
 use crate::game::map::zone::Zone;
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const DEFAULT_INSTANCE_TEARDOWN_SECS: u64 = 300;
+/// Position of the new players in the default zone.
+pub const PLAYER_SPAWN: (f64, f64) = (1.0, 1.0);
 
 pub struct Zones {
+    /// Zones of the world, including the instances of the templates.
     pub zones: HashMap<String, Zone>,
+    pub default_zone: String,
+    templates: HashMap<String, ZoneConfig>,
+    instances: HashMap<String, ZoneInstance>,
+    /// Tile maps by the ID of the zone config.
+    maps: HashMap<String, Arc<TileMap>>,
+    next_instance: InstanceId,
+    quad_tree: QuadTreeConfig,
+    view_distance: f64,
+    instance_teardown: Duration,
+}
+
+impl Zones {
+    /// Loads the tile maps of the zones, and checks that the spawn points of the players and the
+    /// portals are inside their zone and not blocked.
+    pub fn new(
+        zone_configs: &[ZoneConfig],
+        quad_tree: &QuadTreeConfig,
+        view_distance: f64,
+        instance_teardown: Duration,
+        default_zone: &str,
+    ) -> Result<Self, Error> {
+        let mut zones = Zones {
+            zones: HashMap::new(),
+            default_zone: default_zone.to_string(),
+            templates: HashMap::new(),
+            instances: HashMap::new(),
+            maps: HashMap::new(),
+            next_instance: 1,
+            quad_tree: quad_tree.clone(),
+            view_distance,
+            instance_teardown,
+        };
+        for zone in zone_configs {
+            if let Some(path) = &zone.map {
+                let map = TileMap::load(path, Position::from_coord(zone.min.0, zone.min.1))?;
+                zones.maps.insert(zone.id.clone(), Arc::new(map));
+            }
+            if zone.instanced {
+                zones.templates.insert(zone.id.clone(), zone.clone());
+            } else {
+                let built = zones.build(zone.id.clone(), zone);
+                zones.zones.insert(zone.id.clone(), built);
+            }
+        }
+
+        zones.validate_spawn(zone_configs, default_zone, PLAYER_SPAWN)?;
+        for zone in zone_configs {
+            for portal in &zone.portals {
+                zones.validate_spawn(zone_configs, &portal.destination, portal.spawn)?;
+            }
+        }
+        Ok(zones)
+    }
+
+    pub fn is_template(&self, id: &str) -> bool {
+        self.templates.contains_key(id)
+    }
+
+    /// Returns the instance, if the zone is a copy of a template.
+    pub fn instance(&self, zone_id: &str) -> Option<&ZoneInstance> {
+        self.instances.get(zone_id)
+    }
+
+    /// Finds the copy of the template belonging to the owner, a new one is created when it has
+    /// none yet.
+    ///
+    /// # Returns
+    /// The zone ID of the instance, or None if the template does not exist.
+    pub fn instance_of(&mut self, template: &str, owner: InstanceOwner) -> Option<String> {
+        if let Some((zone_id, _)) = self
+            .instances
+            .iter()
+            .find(|(_, i)| i.template == template && i.owner == owner)
+        {
+            return Some(zone_id.clone());
+        }
+
+        let config = self.templates.get(template)?.clone();
+        let id = self.next_instance;
+        self.next_instance += 1;
+        let zone_id = format!("{}#{}", template, id);
+        let zone = self.build(zone_id.clone(), &config);
+        self.zones.insert(zone_id.clone(), zone);
+        self.instances.insert(
+            zone_id.clone(),
+            ZoneInstance::new(id, template.to_string(), owner),
+        );
+        info!("Created instance {} for {:?}", &zone_id, owner);
+        Some(zone_id)
+    }
+
+    /// Removes the instances, that have been empty for longer than the teardown time.
+    ///
+    /// # Returns
+    /// The zone IDs of the removed instances.
+    pub fn tear_down_instances(&mut self, now: Instant) -> Vec<String> {
+        let zones = &self.zones;
+        let teardown = self.instance_teardown;
+        let abandoned: Vec<String> = self
+            .instances
+            .iter_mut()
+            .filter_map(|(zone_id, instance)| {
+                let empty = zones.get(zone_id).is_none_or(|z| z.grid.is_empty());
+                if instance.is_abandoned(empty, now, teardown) {
+                    Some(zone_id.clone())
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        for zone_id in &abandoned {
+            self.instances.remove(zone_id);
+            self.zones.remove(zone_id);
+            info!("Instance {} is torn down", zone_id);
+        }
+        abandoned
+    }
+
+    fn validate_spawn(
+        &self,
+        zone_configs: &[ZoneConfig],
+        zone_id: &str,
+        spawn: (f64, f64),
+    ) -> Result<(), Error> {
+        let position = Position::from_coord(spawn.0, spawn.1);
+        let zone = zone_configs
+            .iter()
+            .find(|z| z.id == zone_id)
+            .ok_or_else(|| Error::ConfigError(format!("Zone {} is not defined", zone_id)))?;
+        if !zone.area().contains(position) {
+            return Err(Error::ConfigError(format!(
+                "Spawn point {:?} is outside of zone {}",
+                spawn, zone_id
+            )));
+        }
+        if self.maps.get(zone_id).is_some_and(|map| map.is_blocked(position)) {
+            return Err(Error::MapError(format!(
+                "Spawn point {:?} of zone {} is blocked",
+                spawn, zone_id
+            )));
+        }
+        Ok(())
+    }
+
+    fn build(&self, id: String, zone: &ZoneConfig) -> Zone {
+        Zone::new(
+            id,
+            QuadTree::new(
+                Position::from_coord(zone.min.0, zone.min.1),
+                Position::from_coord(zone.max.0, zone.max.1),
+                self.quad_tree.bucket_size,
+                self.quad_tree.max_depth,
+            ),
+            self.view_distance,
+            zone.portals
+                .iter()
+                .map(|p| {
+                    Portal::new(
+                        p.area(),
+                        p.destination.clone(),
+                        Position::from_coord(p.spawn.0, p.spawn.1),
+                    )
+                })
+                .collect(),
+            self.maps.get(&zone.id).cloned(),
+        )
+    }
 }
 
 impl Default for Zones {
@@ -15,9 +195,215 @@ impl Default for Zones {
             Zone::new(
                 "1".to_string(),
                 QuadTree::new(Position::new(), Position::from_coord(1000.0, 1000.0), 50, 4),
+                100.0,
+                Vec::new(),
+                None,
             ),
         );
 
-        Zones { zones }
+        Zones {
+            zones,
+            default_zone: "1".to_string(),
+            templates: HashMap::new(),
+            instances: HashMap::new(),
+            maps: HashMap::new(),
+            next_instance: 1,
+            quad_tree: QuadTreeConfig::default(),
+            view_distance: 100.0,
+            instance_teardown: Duration::from_secs(DEFAULT_INSTANCE_TEARDOWN_SECS),
+        }
+    }
+}
+
+/// Setup shared by the tests of the systems working with zones.
+#[cfg(test)]
+pub mod test_util {
+    use crate::common::obj_id::GameObjectIdentifier;
+    use crate::config::config::{QuadTreeConfig, ZoneConfig};
+    use crate::game::components::obj::GameObjectDescriptor;
+    use crate::game::location::pos::{LocatableGameObject, Position};
+    use crate::game::resource::zones::Zones;
+    use legion::{Entity, World};
+    use std::time::Duration;
+
+    /// The default zone of the test zones.
+    pub const TEST_ZONE: &str = "1";
+
+    /// A zone from (0, 0) to (1000, 1000) without portals and tile map.
+    pub fn zone_config(id: &str) -> ZoneConfig {
+        ZoneConfig {
+            id: id.to_string(),
+            min: (0.0, 0.0),
+            max: (1000.0, 1000.0),
+            instanced: false,
+            map: None,
+            portals: Vec::new(),
+        }
+    }
+
+    /// Zones with a view distance of 100, the default zone is `TEST_ZONE`.
+    pub fn test_zones(configs: &[ZoneConfig], bucket_size: usize, max_depth: usize) -> Zones {
+        Zones::new(
+            configs,
+            &QuadTreeConfig {
+                bucket_size,
+                max_depth,
+            },
+            100.0,
+            Duration::from_secs(300),
+            TEST_ZONE,
+        )
+        .expect("Valid test zones")
+    }
+
+    /// Puts the entity into the grid of `TEST_ZONE` at the position and adds its descriptor.
+    pub fn place(
+        world: &mut World,
+        zones: &mut Zones,
+        entity: Entity,
+        network: u32,
+        position: Position,
+    ) -> GameObjectIdentifier {
+        let id = GameObjectIdentifier::new(entity, network.to_string(), network);
+        zones
+            .zones
+            .get_mut(TEST_ZONE)
+            .expect("Test zone")
+            .grid
+            .add(network, LocatableGameObject::new(id.clone(), position));
+        world
+            .entry(entity)
+            .expect("Entity exists")
+            .add_component(GameObjectDescriptor::new(id.clone(), TEST_ZONE.to_string()));
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::obj_id::GameObjectIdentifier;
+    use crate::config::config::{PortalConfig, QuadTreeConfig, ZoneConfig};
+    use crate::error::error::Error;
+    use crate::game::location::pos::{LocatableGameObject, Position};
+    use crate::game::map::instance::InstanceOwner;
+    use crate::game::resource::zones::test_util::zone_config;
+    use crate::game::resource::zones::Zones;
+    use legion::World;
+    use std::fs;
+    use std::time::{Duration, Instant};
+
+    fn zones() -> Zones {
+        let zone = |id: &str, instanced| ZoneConfig {
+            instanced,
+            ..zone_config(id)
+        };
+        Zones::new(
+            &[zone("1", false), zone("dungeon", true)],
+            &QuadTreeConfig::default(),
+            100.0,
+            Duration::from_secs(60),
+            "1",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_instance_per_owner() {
+        let mut zones = zones();
+        assert!(zones.is_template("dungeon"));
+        assert!(!zones.zones.contains_key("dungeon"));
+        assert!(zones.instance_of("1", InstanceOwner::Party(1)).is_none());
+
+        let mut world = World::default();
+        let owner = world.push(());
+        let party = zones.instance_of("dungeon", InstanceOwner::Party(1)).unwrap();
+        let player = zones
+            .instance_of("dungeon", InstanceOwner::Player(owner))
+            .unwrap();
+        assert_ne!(party, player);
+        assert_eq!(
+            zones.instance_of("dungeon", InstanceOwner::Party(1)),
+            Some(party.clone())
+        );
+        assert_eq!(zones.instance(&party).unwrap().template, "dungeon");
+        assert_ne!(
+            zones.instance(&party).unwrap().id,
+            zones.instance(&player).unwrap().id
+        );
+
+        let id = GameObjectIdentifier::new(world.push(()), "1".to_string(), 1);
+        zones
+            .zones
+            .get_mut(&party)
+            .unwrap()
+            .grid
+            .add(1, LocatableGameObject::new(id, Position::from_coord(1.0, 1.0)));
+        assert!(zones.zones[&player].grid.is_empty());
+    }
+
+    #[test]
+    fn test_tear_down_empty_instance() {
+        let mut zones = zones();
+        let instance = zones.instance_of("dungeon", InstanceOwner::Party(1)).unwrap();
+        let mut world = World::default();
+        let id = GameObjectIdentifier::new(world.push(()), "1".to_string(), 1);
+        zones
+            .zones
+            .get_mut(&instance)
+            .unwrap()
+            .grid
+            .add(1, LocatableGameObject::new(id, Position::from_coord(1.0, 1.0)));
+
+        let start = Instant::now();
+        assert!(zones.tear_down_instances(start).is_empty());
+        assert!(zones
+            .tear_down_instances(start + Duration::from_secs(120))
+            .is_empty());
+
+        zones.zones.get_mut(&instance).unwrap().grid.remove(&1);
+        assert!(zones
+            .tear_down_instances(start + Duration::from_secs(150))
+            .is_empty());
+        assert_eq!(
+            zones.tear_down_instances(start + Duration::from_secs(210)),
+            vec![instance.clone()]
+        );
+        assert!(!zones.zones.contains_key(&instance));
+        assert!(zones.instance(&instance).is_none());
+    }
+
+    #[test]
+    fn test_blocked_spawn() {
+        let path = std::env::temp_dir().join(format!("bb_spawn_{}.map", std::process::id()));
+        // The player spawn (1, 1) and the upper half are walkable
+        fs::write(&path, "500\n.#\n..").unwrap();
+        let portal = |spawn| PortalConfig {
+            min: (900.0, 0.0),
+            max: (1000.0, 100.0),
+            destination: "1".to_string(),
+            spawn,
+        };
+        let build = |spawn| {
+            let zone = ZoneConfig {
+                map: Some(path.clone()),
+                portals: vec![portal(spawn)],
+                ..zone_config("1")
+            };
+            Zones::new(
+                &[zone],
+                &QuadTreeConfig::default(),
+                100.0,
+                Duration::from_secs(60),
+                "1",
+            )
+        };
+
+        assert!(build((250.0, 750.0)).is_ok());
+        assert!(matches!(build((750.0, 250.0)), Err(Error::MapError(_))));
+        assert!(matches!(build((250.0, 1000.0)), Err(Error::ConfigError(_))));
+
+        fs::write(&path, "500\n#.\n..").unwrap();
+        assert!(matches!(build((250.0, 750.0)), Err(Error::MapError(_))));
+        fs::remove_file(&path).ok();
     }
 }
@@ -0,0 +1,51 @@
+use crate::game::components::connection::NetworkConnectionComponent;
+use crate::game::resource::heartbeat::HeartbeatSettings;
+use crate::game::resource::user_manager::UserManagerStorage;
+use crate::net::packet::heartbeat::HeartbeatPacket;
+use crate::net::packet::packet::S2CPacketBuilder;
+use crate::net::protocol::encode::BBEncodable;
+use crate::net::protocol::opcode::NetworkSendOpCode;
+use crate::user::user::AuthenticatedUser;
+use bytes::BytesMut;
+use legion::system;
+use std::time::Instant;
+
+#[system(for_each)]
+pub fn heartbeat(
+    #[resource] settings: &HeartbeatSettings,
+    #[resource] users: &mut UserManagerStorage,
+    conn: &mut NetworkConnectionComponent,
+) {
+    let conn: &mut AuthenticatedUser = &mut conn.user;
+    if conn.writer.is_none() {
+        return;
+    }
+    let now = Instant::now();
+
+    if conn.heartbeat.is_timed_out(now, settings.timeout) {
+        warn!(
+            "No data received from {} for {:?}, dropping connection",
+            conn, settings.timeout
+        );
+        conn.reader.take();
+        conn.writer.take();
+        users.disconnected_users.push_front(conn.addr);
+        return;
+    }
+
+    if conn.heartbeat.is_ping_due(now, settings.ping_interval) {
+        let seq = conn.heartbeat.ping(now);
+        let ping = HeartbeatPacket::new(seq);
+        if let (Some(writer), Ok(packet)) = (
+            &mut conn.writer,
+            S2CPacketBuilder::new()
+                .op_code(NetworkSendOpCode::PING)
+                .data(&ping)
+                .build(),
+        ) {
+            let mut buf = BytesMut::new();
+            packet.encode_as_bbp(&mut buf);
+            writer.queue(buf.freeze());
+        }
+    }
+}
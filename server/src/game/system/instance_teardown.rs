@@ -0,0 +1,9 @@
+use crate::game::resource::zones::Zones;
+use legion::system;
+use std::time::Instant;
+
+/// Removes the zone instances, that stayed empty for too long.
+#[system]
+pub fn instance_teardown(#[resource] zones: &mut Zones) {
+    zones.tear_down_instances(Instant::now());
+}
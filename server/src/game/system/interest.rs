@@ -0,0 +1,328 @@
+use crate::common::obj_id::{GameObjectIdentifier, NetworkId};
+use crate::game::components::connection::NetworkConnectionComponent;
+use crate::game::components::movement::{Location, Transformation};
+use crate::game::components::obj::GameObjectDescriptor;
+use crate::game::components::phase::{Phase, PhaseMask, ALL_PHASES};
+use crate::game::components::visibility::Visibility;
+use crate::game::resource::zones::Zones;
+use crate::net::packet::packet::S2CPacketBuilder;
+use crate::net::packet::spawn::SpawnPacket;
+use crate::net::packet::world_snapshot::WorldSnapshotPacket;
+use crate::net::packet::state_delta::{
+    ObjectStateBatch, ObjectStateChange, ObjectStateDeltaPacket,
+};
+use crate::net::protocol::encode::BBEncodable;
+use crate::net::protocol::opcode::NetworkSendOpCode;
+use bytes::BytesMut;
+use legion::world::SubWorld;
+use legion::{system, Entity, EntityStore, IntoQuery};
+use std::collections::HashMap;
+
+/// Objects, that entered and left the view range of a player since the last tick.
+struct InterestChange {
+    viewer: Entity,
+    entered: Vec<GameObjectIdentifier>,
+    left: Vec<GameObjectIdentifier>,
+}
+
+/// Keeps the visible objects of the players in sync with their view range and phases. Clients get
+/// a full snapshot of the objects coming into view and a despawn of the ones leaving it. Players
+/// new to the world get everything in a single world snapshot.
+#[system]
+#[read_component(GameObjectDescriptor)]
+#[read_component(Phase)]
+#[read_component(Location)]
+#[read_component(Transformation)]
+#[write_component(Visibility)]
+#[write_component(NetworkConnectionComponent)]
+pub fn interest(world: &mut SubWorld, #[resource] zones: &Zones) {
+    let changes: Vec<InterestChange> = <(Entity, &GameObjectDescriptor, &Visibility)>::query()
+        .iter(world)
+        .filter_map(|(entity, obj, visibility)| {
+            let in_range: HashMap<NetworkId, &GameObjectIdentifier> = zones
+                .zones
+                .get(&obj.zone_id)?
+                .get_neighbors_of(obj.id.network)?
+                .into_iter()
+                .filter(|o| {
+                    o.id.network == obj.id.network
+                        || visibility.sees(phase_of(world, o.id.internal))
+                })
+                .map(|o| (o.id.network, &o.id))
+                .collect();
+            let entered: Vec<GameObjectIdentifier> = in_range
+                .iter()
+                .filter(|(network, _)| !visibility.is_visible(**network))
+                .map(|(_, id)| (*id).clone())
+                .collect();
+            let left: Vec<GameObjectIdentifier> = visibility
+                .visible
+                .iter()
+                .filter(|(network, _)| !in_range.contains_key(network))
+                .map(|(_, id)| id.clone())
+                .collect();
+            if entered.is_empty() && left.is_empty() {
+                return None;
+            }
+            Some(InterestChange {
+                viewer: *entity,
+                entered,
+                left,
+            })
+        })
+        .collect();
+
+    for change in changes {
+        let mut packets = Vec::new();
+        let mut shown = Vec::new();
+        for id in change.entered {
+            // Objects created in this tick do not exist in the world yet, they are shown later
+            if let Some(snapshot) = snapshot_of(world, &id) {
+                packets.push(snapshot);
+                shown.push(id);
+            }
+        }
+        for id in &change.left {
+            let mut batch = ObjectStateBatch::new();
+            batch.add(ObjectStateChange::DeSpawn);
+            packets.push(ObjectStateDeltaPacket::new(id.clone(), batch));
+        }
+
+        if let Ok(mut entry) = world.entry_mut(change.viewer) {
+            let mut first_snapshot = false;
+            if let Ok(visibility) = entry.get_component_mut::<Visibility>() {
+                for id in &change.left {
+                    visibility.visible.remove(&id.network);
+                }
+                for id in shown {
+                    visibility.visible.insert(id.network, id);
+                }
+                first_snapshot = !visibility.snapshot_sent;
+                visibility.snapshot_sent = true;
+            }
+            if let Ok(conn) = entry.get_component_mut::<NetworkConnectionComponent>() {
+                if let Some(writer) = &mut conn.user.writer {
+                    let mut encoded = Vec::new();
+                    if first_snapshot {
+                        encoded.push(encode_packet(
+                            NetworkSendOpCode::WORLD_SNAPSHOT,
+                            &WorldSnapshotPacket::new(packets),
+                        ));
+                    } else {
+                        for packet in &packets {
+                            encoded.push(encode_packet(
+                                NetworkSendOpCode::PLAYER_STATE_CHANGE,
+                                packet,
+                            ));
+                        }
+                    }
+                    for buf in encoded.into_iter().flatten() {
+                        writer.queue(buf.freeze());
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn encode_packet<T: BBEncodable>(op_code: NetworkSendOpCode, data: &T) -> Option<BytesMut> {
+    let packet = S2CPacketBuilder::new().op_code(op_code).data(data).build().ok()?;
+    let mut buf = BytesMut::new();
+    packet.encode_as_bbp(&mut buf);
+    Some(buf)
+}
+
+/// Phases of an object, the ones without a phase are in all of them.
+pub fn phase_of<W: EntityStore>(world: &W, entity: Entity) -> PhaseMask {
+    world
+        .entry_ref(entity)
+        .ok()
+        .and_then(|entry| entry.get_component::<Phase>().ok().map(|p| p.mask))
+        .unwrap_or(ALL_PHASES)
+}
+
+/// Builds the full state of an object, that a client needs to spawn it.
+pub fn snapshot_of<W: EntityStore>(
+    world: &W,
+    id: &GameObjectIdentifier,
+) -> Option<ObjectStateDeltaPacket> {
+    let entry = world.entry_ref(id.internal).ok()?;
+    let location = entry.get_component::<Location>().ok()?;
+    let transformation = entry.get_component::<Transformation>().ok()?;
+
+    let mut batch = ObjectStateBatch::new();
+    batch.add(ObjectStateChange::Spawn(SpawnPacket::new(
+        id.external.clone(),
+        location.position,
+    )));
+    batch.add(ObjectStateChange::Facing(
+        transformation.facing.get_facing(),
+    ));
+    batch.add(ObjectStateChange::Speed(transformation.current_speed()));
+    Some(ObjectStateDeltaPacket::new(id.clone(), batch))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::components::connection::NetworkConnectionComponent;
+    use crate::game::components::movement::{Location, Transformation};
+    use crate::game::components::phase::Phase;
+    use crate::game::components::visibility::Visibility;
+    use crate::game::location::pos::Position;
+    use crate::game::resource::zones::test_util::{place, test_zones, zone_config};
+    use crate::game::resource::zones::Zones;
+    use crate::game::system::interest::interest_system;
+    use crate::game::system::zone_sync::zone_sync_system;
+    use crate::net::protocol::cursor::ByteCursor;
+    use crate::net::protocol::opcode::NetworkSendOpCode;
+    use crate::net::provider::DataStreamWriter;
+    use crate::user::user::AuthenticatedUser;
+    use bytes::BytesMut;
+    use crossbeam_channel::unbounded;
+    use legion::{Entity, EntityStore, Resources, Schedule, World};
+
+    fn spawn(world: &mut World, zones: &mut Zones, network: u32, x: f64, y: f64) -> Entity {
+        let position = Position::from_coord(x, y);
+        let entity = world.push((
+            Location { position },
+            Transformation::new(1.0, 1.0),
+            Visibility::new(),
+        ));
+        place(world, zones, entity, network, position);
+        entity
+    }
+
+    fn flush(world: &mut World, entity: Entity) {
+        let mut entry = world.entry(entity).unwrap();
+        let conn = entry
+            .get_component_mut::<NetworkConnectionComponent>()
+            .unwrap();
+        conn.user.writer.as_mut().unwrap().flush(1400).unwrap();
+    }
+
+    fn visible(world: &World, entity: Entity) -> Vec<u32> {
+        let entry = world.entry_ref(entity).unwrap();
+        let mut visible: Vec<u32> = entry
+            .get_component::<Visibility>()
+            .unwrap()
+            .visible
+            .keys()
+            .cloned()
+            .collect();
+        visible.sort();
+        visible
+    }
+
+    #[test]
+    fn test_enter_and_leave_view_range() {
+        let mut world = World::default();
+        let mut zones = test_zones(&[zone_config("1")], 2, 1);
+        let first = spawn(&mut world, &mut zones, 1, 450.0, 100.0);
+        let second = spawn(&mut world, &mut zones, 2, 520.0, 100.0);
+        let far = spawn(&mut world, &mut zones, 3, 900.0, 900.0);
+        let mut resources = Resources::default();
+        resources.insert(zones);
+        let mut schedule = Schedule::builder()
+            .add_system(zone_sync_system())
+            .add_system(interest_system())
+            .build();
+
+        schedule.execute(&mut world, &mut resources);
+        assert_eq!(visible(&world, first), vec![1, 2]);
+        assert_eq!(visible(&world, second), vec![1, 2]);
+        assert_eq!(visible(&world, far), vec![3]);
+
+        world
+            .entry(second)
+            .unwrap()
+            .get_component_mut::<Location>()
+            .unwrap()
+            .position = Position::from_coord(850.0, 850.0);
+        schedule.execute(&mut world, &mut resources);
+        assert_eq!(visible(&world, first), vec![1]);
+        assert_eq!(visible(&world, second), vec![2, 3]);
+        assert_eq!(visible(&world, far), vec![2, 3]);
+    }
+
+    #[test]
+    fn test_world_snapshot_on_first_tick() {
+        let mut world = World::default();
+        let mut zones = test_zones(&[zone_config("1")], 10, 1);
+        let player = spawn(&mut world, &mut zones, 1, 100.0, 100.0);
+        spawn(&mut world, &mut zones, 2, 150.0, 100.0);
+        let (sender, receiver) = unbounded();
+        let user = AuthenticatedUser::new(
+            "127.0.0.1:1".parse().unwrap(),
+            "1".to_string(),
+            None,
+            Some(DataStreamWriter::new(sender)),
+        );
+        world
+            .entry(player)
+            .unwrap()
+            .add_component(NetworkConnectionComponent::new(user));
+        let mut resources = Resources::default();
+        resources.insert(zones);
+        let mut schedule = Schedule::builder().add_system(interest_system()).build();
+
+        schedule.execute(&mut world, &mut resources);
+        flush(&mut world, player);
+        let frame = BytesMut::from(&receiver.try_recv().expect("Snapshot sent")[4..]);
+        let mut cursor = ByteCursor::new(&frame);
+        assert_eq!(
+            cursor.as_u16(),
+            Some(NetworkSendOpCode::WORLD_SNAPSHOT as u16)
+        );
+        assert_eq!(cursor.as_u32(), Some(2));
+        assert!(receiver.try_recv().is_err());
+
+        spawn(
+            &mut world,
+            &mut resources.get_mut::<Zones>().unwrap(),
+            3,
+            100.0,
+            150.0,
+        );
+        schedule.execute(&mut world, &mut resources);
+        flush(&mut world, player);
+        let frame = BytesMut::from(&receiver.try_recv().expect("Spawn sent")[4..]);
+        let mut cursor = ByteCursor::new(&frame);
+        assert_eq!(
+            cursor.as_u16(),
+            Some(NetworkSendOpCode::PLAYER_STATE_CHANGE as u16)
+        );
+        assert_eq!(cursor.as_u32(), Some(3));
+    }
+
+    #[test]
+    fn test_phases() {
+        let mut world = World::default();
+        let mut zones = Zones::default();
+        let player = spawn(&mut world, &mut zones, 1, 100.0, 100.0);
+        let before_quest = spawn(&mut world, &mut zones, 2, 120.0, 100.0);
+        let after_quest = spawn(&mut world, &mut zones, 3, 140.0, 100.0);
+        world
+            .entry(before_quest)
+            .unwrap()
+            .add_component(Phase::new(0b01));
+        world
+            .entry(after_quest)
+            .unwrap()
+            .add_component(Phase::new(0b10));
+        let mut resources = Resources::default();
+        resources.insert(zones);
+        let mut schedule = Schedule::builder().add_system(interest_system()).build();
+
+        schedule.execute(&mut world, &mut resources);
+        assert_eq!(visible(&world, player), vec![1, 2]);
+
+        world
+            .entry(player)
+            .unwrap()
+            .get_component_mut::<Visibility>()
+            .unwrap()
+            .phases = 0b10;
+        schedule.execute(&mut world, &mut resources);
+        assert_eq!(visible(&world, player), vec![1, 3]);
+    }
+}
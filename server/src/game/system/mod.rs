@@ -1,4 +1,10 @@
 pub mod user_input;
 pub mod movement;
+pub mod heartbeat;
 pub mod user_change;
-pub mod network_stream;
\ No newline at end of file
+pub mod network_stream;
+pub mod zone_sync;
+pub mod interest;
+pub mod zone_transfer;
+pub mod instance_teardown;
+pub mod npc_movement;
\ No newline at end of file
@@ -1,54 +1,202 @@
 use crate::game::components::connection::NetworkConnectionComponent;
 use crate::game::components::obj::GameObjectDescriptor;
-use crate::game::location::pos::LocatableGameObject;
+use crate::game::components::phase::Phase;
+use crate::game::components::visibility::Visibility;
+use crate::game::resource::network::NetworkSettings;
 use crate::game::resource::state_delta::StateDeltaCache;
 use crate::game::resource::zones::Zones;
+use crate::game::system::interest::phase_of;
+use crate::net::packet::packet::S2CPacketBuilder;
 use crate::net::protocol::encode::BBEncodable;
+use crate::net::protocol::opcode::NetworkSendOpCode;
 use bytes::BytesMut;
 use legion::world::SubWorld;
-use legion::{system, EntityStore, Resources, World};
-use std::sync::atomic::AtomicPtr;
-use crate::net::packet::packet::S2CPacketBuilder;
-use crate::net::protocol::opcode::NetworkSendOpCode;
+use legion::{system, Entity, EntityStore, IntoQuery};
 
-pub fn network_stream(mut world: AtomicPtr<World>, mut resources: AtomicPtr<Resources>) {
-    unsafe {
-        let mut resources = &mut (*(*resources.get_mut()));
-        let mut world = &mut (*(*world.get_mut()));
-
-        if let (Some(mut state_delta), Some(mut zones)) = (
-            resources.get_mut::<StateDeltaCache>(),
-            resources.get_mut::<Zones>(),
-        ) {
-            for delta in state_delta.0.drain(0..) {
-                let mut neighbours = None;
-                if let Ok(mut entity) = world.entry_mut(delta.id.internal.clone()) {
-                    if let Ok(obj) = entity.get_component::<GameObjectDescriptor>() {
-                        if let Some(zone) = zones.zones.get_mut(&obj.zone_id) {
-                            neighbours = zone
-                                .get_neighbors_of(obj.id.external.clone())
-                                .map(|n| n.values());
-                        }
-                    }
+/// Sends the state changes of the tick to the players, who see the changed objects and share a
+/// phase with them, then flushes everything queued for the clients.
+#[system]
+#[read_component(GameObjectDescriptor)]
+#[read_component(Phase)]
+#[read_component(Visibility)]
+#[write_component(NetworkConnectionComponent)]
+pub fn network_stream(
+    world: &mut SubWorld,
+    #[resource] state_delta: &mut StateDeltaCache,
+    #[resource] zones: &Zones,
+    #[resource] settings: &NetworkSettings,
+) {
+    for delta in state_delta.0.drain(0..) {
+        let neighbours: Vec<Entity> = match world
+            .entry_ref(delta.id.internal)
+            .ok()
+            .and_then(|entry| {
+                let obj = entry.get_component::<GameObjectDescriptor>().ok()?;
+                let zone = zones.zones.get(&obj.zone_id)?;
+                zone.get_neighbors_of(obj.id.network)
+                    .map(|n| n.iter().map(|o| o.id.internal).collect())
+            }) {
+            Some(neighbours) => neighbours,
+            None => continue,
+        };
+        let packet = match S2CPacketBuilder::new()
+            .op_code(NetworkSendOpCode::PLAYER_STATE_CHANGE)
+            .data(&delta)
+            .build()
+        {
+            Ok(packet) => packet,
+            Err(_) => continue,
+        };
+        let mut buf = BytesMut::new();
+        packet.encode_as_bbp(&mut buf);
+        let buf = buf.freeze();
+        let phases = phase_of(world, delta.id.internal);
+
+        for neighbour in neighbours {
+            if let Ok(mut entity) = world.entry_mut(neighbour) {
+                // Clients learn about an object from its snapshot, when it comes into view
+                let visible = entity
+                    .get_component::<Visibility>()
+                    .is_ok_and(|v| {
+                        v.is_visible(delta.id.network)
+                            && (neighbour == delta.id.internal || v.sees(phases))
+                    });
+                if !visible {
+                    continue;
                 }
-                if let Some(neighbours) = neighbours {
-                    for neighbour in neighbours {
-                        if let Ok(mut entity) = world.entry_mut(neighbour.id.internal) {
-                            if let Ok(network_connection) =
-                                entity.get_component_mut::<NetworkConnectionComponent>()
-                            {
-                                if let Some(writer) = &mut network_connection.user.writer {
-                                    if let Ok (packet) = S2CPacketBuilder::new().op_code(NetworkSendOpCode::PLAYER_STATE_CHANGE).data(&delta).build() {
-                                        let mut buf = BytesMut::new();
-                                        packet.encode_as_bbp(&mut buf);
-                                        writer.send(buf.freeze());
-                                    }
-                                }
-                            }
-                        }
+                if let Ok(network_connection) =
+                    entity.get_component_mut::<NetworkConnectionComponent>()
+                {
+                    if let Some(writer) = &mut network_connection.user.writer {
+                        writer.queue(buf.clone());
                     }
                 }
             }
         }
     }
+
+    // Everything queued during the tick goes out in as few frames as possible
+    for conn in <&mut NetworkConnectionComponent>::query().iter_mut(world) {
+        if let Some(writer) = &mut conn.user.writer {
+            if let Err(e) = writer.flush(settings.max_batch_size) {
+                debug!("Unable to send to {}: {}", conn.user, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::obj_id::GameObjectIdentifier;
+    use crate::game::components::connection::NetworkConnectionComponent;
+    use crate::game::components::phase::Phase;
+    use crate::game::components::visibility::Visibility;
+    use crate::game::location::pos::Position;
+    use crate::game::resource::network::NetworkSettings;
+    use crate::game::resource::state_delta::StateDeltaCache;
+    use crate::game::resource::zones::test_util::place;
+    use crate::game::resource::zones::Zones;
+    use crate::game::system::network_stream::network_stream_system;
+    use crate::net::packet::state_delta::{
+        ObjectStateBatch, ObjectStateChange, ObjectStateDeltaPacket,
+    };
+    use crate::net::protocol::cursor::ByteCursor;
+    use crate::net::protocol::opcode::NetworkSendOpCode;
+    use crate::net::provider::DataStreamWriter;
+    use crate::user::user::AuthenticatedUser;
+    use bytes::{Bytes, BytesMut};
+    use crossbeam_channel::{unbounded, Receiver};
+    use legion::{Resources, Schedule, World};
+
+    fn spawn(
+        world: &mut World,
+        zones: &mut Zones,
+        network: u32,
+        x: f64,
+    ) -> (GameObjectIdentifier, Receiver<Bytes>) {
+        let (sender, receiver) = unbounded();
+        let user = AuthenticatedUser::new(
+            "127.0.0.1:1".parse().unwrap(),
+            network.to_string(),
+            None,
+            Some(DataStreamWriter::new(sender)),
+        );
+        let entity = world.push((NetworkConnectionComponent::new(user), Visibility::new()));
+        let id = place(world, zones, entity, network, Position::from_coord(x, 100.0));
+        (id, receiver)
+    }
+
+    #[test]
+    fn test_delta_sent_to_viewers() {
+        let mut world = World::default();
+        let mut zones = Zones::default();
+        let (moving, _) = spawn(&mut world, &mut zones, 1, 100.0);
+        let (viewer, viewer_receiver) = spawn(&mut world, &mut zones, 2, 150.0);
+        let (_, stranger_receiver) = spawn(&mut world, &mut zones, 3, 160.0);
+        world
+            .entry(viewer.internal)
+            .unwrap()
+            .get_component_mut::<Visibility>()
+            .unwrap()
+            .visible
+            .insert(moving.network, moving.clone());
+
+        let mut batch = ObjectStateBatch::new();
+        batch.add(ObjectStateChange::Speed(1.0));
+        let mut state_delta = StateDeltaCache::new();
+        state_delta
+            .0
+            .push_back(ObjectStateDeltaPacket::new(moving, batch));
+        let mut resources = Resources::default();
+        resources.insert(zones);
+        resources.insert(state_delta);
+        resources.insert(NetworkSettings::new(1400));
+        Schedule::builder()
+            .add_system(network_stream_system())
+            .build()
+            .execute(&mut world, &mut resources);
+
+        let frame = BytesMut::from(&viewer_receiver.try_recv().expect("Delta sent")[4..]);
+        let mut cursor = ByteCursor::new(&frame);
+        assert_eq!(
+            cursor.as_u16(),
+            Some(NetworkSendOpCode::PLAYER_STATE_CHANGE as u16)
+        );
+        assert_eq!(cursor.as_u32(), Some(1));
+        assert!(stranger_receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_delta_not_sent_to_other_phase() {
+        let mut world = World::default();
+        let mut zones = Zones::default();
+        let (npc, _) = spawn(&mut world, &mut zones, 1, 100.0);
+        let (viewer, viewer_receiver) = spawn(&mut world, &mut zones, 2, 150.0);
+        world
+            .entry(npc.internal)
+            .unwrap()
+            .add_component(Phase::new(0b10));
+        world
+            .entry(viewer.internal)
+            .unwrap()
+            .get_component_mut::<Visibility>()
+            .unwrap()
+            .visible
+            .insert(npc.network, npc.clone());
+
+        let mut batch = ObjectStateBatch::new();
+        batch.add(ObjectStateChange::Speed(1.0));
+        let mut state_delta = StateDeltaCache::new();
+        state_delta.0.push_back(ObjectStateDeltaPacket::new(npc, batch));
+        let mut resources = Resources::default();
+        resources.insert(zones);
+        resources.insert(state_delta);
+        resources.insert(NetworkSettings::new(1400));
+        Schedule::builder()
+            .add_system(network_stream_system())
+            .build()
+            .execute(&mut world, &mut resources);
+
+        assert!(viewer_receiver.try_recv().is_err());
+    }
 }
@@ -0,0 +1,109 @@
+use crate::game::behaviour::npc::{Walker, WalkerStateData};
+use crate::game::components::movement::{Location, Transformation};
+use crate::game::components::navigation::Navigation;
+use crate::game::components::obj::GameObjectDescriptor;
+use crate::game::components::state::StateMachineComponent;
+use crate::game::resource::frame::FrameResource;
+use crate::game::resource::state_delta::StateDeltaCache;
+use crate::game::resource::zones::Zones;
+use crate::net::packet::state_delta::ObjectStateDeltaPacket;
+use legion::world::SubWorld;
+use legion::{system, IntoQuery};
+
+/// Moves the NPCs along the paths to their destinations.
+#[system]
+#[read_component(GameObjectDescriptor)]
+#[write_component(Transformation)]
+#[write_component(Location)]
+#[write_component(Navigation)]
+#[write_component(StateMachineComponent<Walker>)]
+pub fn npc_movement(
+    world: &mut SubWorld,
+    #[resource] frame: &FrameResource,
+    #[resource] zones: &mut Zones,
+    #[resource] state_delta: &mut StateDeltaCache,
+) {
+    let mut query = <(
+        &GameObjectDescriptor,
+        &mut Transformation,
+        &mut Location,
+        &mut Navigation,
+        &mut StateMachineComponent<Walker>,
+    )>::query();
+    for (obj, transformation, location, navigation, state) in query.iter_mut(world) {
+        let mut data = WalkerStateData::new(
+            transformation,
+            location,
+            navigation,
+            zones.zones.get_mut(&obj.zone_id),
+            frame.frame_delta,
+        );
+        state.update(&mut data);
+        if !data.state_delta.batch.is_empty() {
+            state_delta.0.push_back(ObjectStateDeltaPacket::new(
+                obj.id.clone(),
+                data.state_delta,
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::obj_id::GameObjectIdentifier;
+    use crate::game::behaviour::npc::npc_movement_machine;
+    use crate::game::components::movement::{Location, Transformation};
+    use crate::game::components::navigation::Navigation;
+    use crate::game::components::obj::GameObjectDescriptor;
+    use crate::game::components::state::StateMachineComponent;
+    use crate::game::location::pos::Position;
+    use crate::game::resource::frame::FrameResource;
+    use crate::game::resource::state_delta::StateDeltaCache;
+    use crate::game::resource::zones::Zones;
+    use crate::game::system::npc_movement::npc_movement_system;
+    use legion::{EntityStore, Resources, Schedule, World};
+    use std::time::Duration;
+
+    #[test]
+    fn test_npc_walks_to_destination() {
+        let mut world = World::default();
+        let mut navigation = Navigation::new();
+        navigation.go_to(Position::from_coord(10.0, 1.0));
+        let entity = world.push((
+            Transformation::new(5.0, 0.0),
+            Location {
+                position: Position::from_coord(1.0, 1.0),
+            },
+            navigation,
+            StateMachineComponent::new(vec![npc_movement_machine()]),
+        ));
+        let id = GameObjectIdentifier::new(entity, "npc".to_string(), 1);
+        world
+            .entry(entity)
+            .unwrap()
+            .add_component(GameObjectDescriptor::new(id, "1".to_string()));
+        let mut resources = Resources::default();
+        resources.insert(FrameResource::new(Duration::from_secs(1)));
+        resources.insert(Zones::default());
+        resources.insert(StateDeltaCache::new());
+        let mut schedule = Schedule::builder()
+            .add_system(npc_movement_system())
+            .build();
+
+        for _ in 0..4 {
+            schedule.execute(&mut world, &mut resources);
+        }
+
+        let entry = world.entry_ref(entity).unwrap();
+        assert_eq!(
+            entry.get_component::<Location>().unwrap().position.x(),
+            10.0
+        );
+        assert!(entry
+            .get_component::<Navigation>()
+            .unwrap()
+            .destination
+            .is_none());
+        assert_eq!(resources.get::<StateDeltaCache>().unwrap().0.len(), 4);
+    }
+}
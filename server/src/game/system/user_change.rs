@@ -3,80 +3,151 @@ use crate::game::components::connection::NetworkConnectionComponent;
 use crate::game::components::input_cache::MovementInputCache;
 use crate::game::components::movement::{Location, Transformation};
 use crate::game::components::obj::GameObjectDescriptor;
-use crate::game::components::state::{MovableStateData, StateMachineComponent};
+use crate::game::behaviour::movement::movement_machine;
+use crate::game::components::state::StateMachineComponent;
+use crate::game::components::visibility::Visibility;
 use crate::game::location::facing::Facing;
 use crate::game::location::pos::{LocatableGameObject, Position};
-use crate::game::resource::state_delta::StateDeltaCache;
+use crate::game::resource::network_id::NetworkIdRegistry;
+use crate::game::resource::session::SessionSettings;
 use crate::game::resource::user_manager::UserManagerStorage;
-use crate::game::resource::zones::Zones;
-use crate::net::packet::spawn::SpawnPacket;
-use crate::net::packet::state_delta::{
-    ObjectStateBatch, ObjectStateChange, ObjectStateDeltaPacket,
-};
+use crate::game::resource::zones::{Zones, PLAYER_SPAWN};
+use crate::net::protocol::encode::BBEncodable;
+use crate::user::auth::AuthPackage;
+use crate::user::user::AuthenticatedUser;
+use bytes::BytesMut;
 use legion::system;
 use legion::systems::CommandBuffer;
 use legion::world::SubWorld;
-use legion::Query;
+use legion::EntityStore;
 use std::borrow::{Borrow, BorrowMut};
+use std::time::Instant;
 
 #[system]
+#[write_component(NetworkConnectionComponent)]
+#[write_component(Visibility)]
+#[read_component(GameObjectDescriptor)]
 pub fn manage_users(
+    world: &mut SubWorld,
     cmd: &mut CommandBuffer,
     #[resource] users: &mut UserManagerStorage,
     #[resource] zones: &mut Zones,
-    #[resource] state_delta: &mut StateDeltaCache,
+    #[resource] sessions: &SessionSettings,
+    #[resource] network_ids: &mut NetworkIdRegistry,
 ) {
-    for user in users.new_users.drain(0..) {
+    let new_users: Vec<AuthenticatedUser> = users.new_users.drain(0..).collect();
+    for user in new_users {
+        let user = match users.name_to_id.get(&user.name).cloned() {
+            Some(id) => match reattach_user(world, users, &id, user) {
+                Ok(()) => continue,
+                Err(user) => user,
+            },
+            None => user,
+        };
         info!("Adding new user {}", &user.name);
         let id = user.name.clone();
         let addr = user.addr.clone();
+        let spawn_position = Position::from_coord(PLAYER_SPAWN.0, PLAYER_SPAWN.1);
         let entity = cmd.push((
             Location {
-                position: Position::from_coord(1.0, 1.0),
-            },
-            Transformation {
-                speed: 1.0,
-                facing: Facing::new(),
+                position: spawn_position,
             },
+            Transformation::new(1.0, std::f32::consts::PI),
             NetworkConnectionComponent::new(user),
-            StateMachineComponent::<MovableStateData>::new(),
+            StateMachineComponent::new(vec![movement_machine()]),
             MovementInputCache::new(),
+            Visibility::new(),
         ));
-        let obj_id = GameObjectIdentifier::new(entity, id.clone());
+        let obj_id = GameObjectIdentifier::new(entity, id.clone(), network_ids.allocate());
         users.socket_to_id.insert(addr, obj_id.clone());
+        users.name_to_id.insert(id.clone(), obj_id.clone());
         cmd.add_component(
             entity,
-            GameObjectDescriptor::new(obj_id.clone(), "1".to_string()),
+            GameObjectDescriptor::new(obj_id.clone(), zones.default_zone.clone()),
         );
-        if let Some(zone) = zones.zones.get_mut("1") {
+        if let Some(zone) = zones.zones.get_mut(&zones.default_zone) {
             zone.grid.add(
-                id.clone(),
-                LocatableGameObject::new(obj_id.clone(), Position::new()),
+                obj_id.network,
+                LocatableGameObject::new(obj_id.clone(), spawn_position),
             );
-            info!("Added {} to zone {}", &obj_id, 1);
-            let mut obj_state = ObjectStateBatch::new();
-            obj_state.add(ObjectStateChange::Spawn(SpawnPacket::new(
-                Position::from_coord(1.0, 1.0),
-            )));
-            state_delta
-                .0
-                .push_back(ObjectStateDeltaPacket::new(obj_id.clone(), obj_state));
+            info!("Added {} to zone {}", &obj_id, &zones.default_zone);
         }
     }
+    let now = Instant::now();
     for user in users.disconnected_users.drain(0..) {
         let id = users.socket_to_id.remove(&user);
         if let Some(id) = id {
-            cmd.remove(id.internal);
-            if let Some(zone) = zones.zones.get_mut("1") {
-                zone.grid.remove(id.external.as_str());
+            if let Ok(mut entry) = world.entry_mut(id.internal) {
+                if let Ok(conn) = entry.get_component_mut::<NetworkConnectionComponent>() {
+                    conn.user.reader.take();
+                    conn.user.writer.take();
+                }
+            }
+            if let Ok(mut registry) = sessions.registry.lock() {
+                registry.disconnect(&id.external, user);
+            }
+            users.link_dead.insert(id.external.clone(), now);
+            info!(
+                "User disconnected {}, keeping it in the world for {:?}",
+                &id, sessions.grace_period
+            );
+        }
+    }
+    for (name, since) in users.take_expired(now, sessions.grace_period) {
+        if let Ok(mut registry) = sessions.registry.lock() {
+            registry.revoke_before(&name, since);
+        }
+        if let Some(id) = users.name_to_id.remove(&name) {
+            let zone_id = world.entry_ref(id.internal).ok().and_then(|entry| {
+                entry
+                    .get_component::<GameObjectDescriptor>()
+                    .ok()
+                    .map(|obj| obj.zone_id.clone())
+            });
+            if let Some(zone) = zone_id.and_then(|zone_id| zones.zones.get_mut(&zone_id)) {
+                zone.grid.remove(&id.network);
             }
-            let mut obj_state = ObjectStateBatch::new();
-            obj_state.add(ObjectStateChange::DeSpawn);
-            state_delta
-                .0
-                .push_back(ObjectStateDeltaPacket::new(id.clone(), obj_state));
+            cmd.remove(id.internal);
+            network_ids.release(id.network);
+            info!("Grace period of {} is over, removed from the world", &id);
+        }
+    }
+}
 
-            info!("User disconnected {}", &id);
+/// Attaches the connection of the user to its existing entity. The previous connection of the
+/// entity, if there is any, is told about the duplicate login and dropped.
+///
+/// # Returns
+/// The user back, if the entity does not exist (anymore).
+fn reattach_user(
+    world: &mut SubWorld,
+    users: &mut UserManagerStorage,
+    id: &GameObjectIdentifier,
+    user: AuthenticatedUser,
+) -> Result<(), AuthenticatedUser> {
+    let mut entry = match world.entry_mut(id.internal) {
+        Ok(entry) => entry,
+        Err(_) => return Err(user),
+    };
+    let conn = match entry.get_component_mut::<NetworkConnectionComponent>() {
+        Ok(conn) => conn,
+        Err(_) => return Err(user),
+    };
+    info!("Reattaching {} to {}", &user, id);
+    if let Some(writer) = &mut conn.user.writer {
+        let mut buf = BytesMut::new();
+        AuthPackage::AUTH_DUPLICATE_LOGIN.encode_as_bbp(&mut buf);
+        if let Err(e) = writer.send(buf.freeze()) {
+            error!("Error while sending duplicate login notice: {}", e.to_string());
         }
     }
+    users.socket_to_id.remove(&conn.user.addr);
+    users.socket_to_id.insert(user.addr, id.clone());
+    users.link_dead.remove(&user.name);
+    conn.user = user;
+    // The new client knows nothing yet, it gets a new world snapshot
+    if let Ok(visibility) = entry.get_component_mut::<Visibility>() {
+        visibility.reset();
+    }
+    Ok(())
 }
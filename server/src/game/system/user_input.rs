@@ -1,8 +1,14 @@
 use crate::game::components::connection::NetworkConnectionComponent;
 use crate::game::components::input_cache::MovementInputCache;
 use crate::net::data::IntermediateGamePacket;
+use crate::net::packet::heartbeat::HeartbeatPacket;
+use crate::net::packet::packet::S2CPacketBuilder;
+use crate::net::protocol::encode::BBEncodable;
+use crate::net::protocol::opcode::NetworkSendOpCode;
 use crate::user::user::AuthenticatedUser;
+use bytes::BytesMut;
 use legion::system;
+use std::time::Instant;
 
 #[system(for_each)]
 pub fn user_input(conn: &mut NetworkConnectionComponent, input_cache: &mut MovementInputCache) {
@@ -12,10 +18,30 @@ pub fn user_input(conn: &mut NetworkConnectionComponent, input_cache: &mut Movem
             let data = reader.try_recv();
             if let Ok(data) = data {
                 debug!("Received data from user: {} {:#?}", conn.name, &data);
+                conn.heartbeat.received(Instant::now());
                 match data {
-                    IntermediateGamePacket::PlayerInput { user, action } => {
+                    IntermediateGamePacket::PlayerInput { action } => {
                         input_cache.movements.push_back(action.clone())
                     }
+                    IntermediateGamePacket::Ping { seq } => {
+                        let pong = HeartbeatPacket::new(seq);
+                        if let (Some(writer), Ok(packet)) = (
+                            &mut conn.writer,
+                            S2CPacketBuilder::new()
+                                .op_code(NetworkSendOpCode::PONG)
+                                .data(&pong)
+                                .build(),
+                        ) {
+                            let mut buf = BytesMut::new();
+                            packet.encode_as_bbp(&mut buf);
+                            writer.queue(buf.freeze());
+                        }
+                    }
+                    IntermediateGamePacket::Pong { seq } => {
+                        if let Some(latency) = conn.heartbeat.pong(seq, Instant::now()) {
+                            debug!("Latency of {}: {:?}", conn.name, latency);
+                        }
+                    }
                     _ => (),
                 }
             } else {
@@ -0,0 +1,74 @@
+use crate::game::components::movement::Location;
+use crate::game::components::obj::GameObjectDescriptor;
+use crate::game::resource::zones::Zones;
+use legion::{maybe_changed, system};
+
+/// Moves the objects of the zone grids along with their entities, so that neighbour lookups are
+/// based on the current positions.
+#[system(for_each)]
+#[filter(maybe_changed::<Location>())]
+pub fn zone_sync(
+    #[resource] zones: &mut Zones,
+    location: &Location,
+    obj: &GameObjectDescriptor,
+) {
+    if let Some(zone) = zones.zones.get_mut(&obj.zone_id) {
+        zone.grid.update_position(&obj.id.network, location.position);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::components::movement::Location;
+    use crate::game::location::pos::Position;
+    use crate::game::resource::zones::test_util::{place, test_zones, zone_config};
+    use crate::game::resource::zones::Zones;
+    use crate::game::system::zone_sync::zone_sync_system;
+    use legion::{Entity, EntityStore, Resources, Schedule, World};
+
+    fn spawn(world: &mut World, zones: &mut Zones, network: u32, x: f64, y: f64) -> Entity {
+        let position = Position::from_coord(x, y);
+        let entity = world.push((Location { position },));
+        place(world, zones, entity, network, position);
+        entity
+    }
+
+    fn node_min(resources: &Resources, network: u32) -> (f64, f64) {
+        let mut zones = resources.get_mut::<Zones>().unwrap();
+        let node = zones
+            .zones
+            .get_mut("1")
+            .unwrap()
+            .grid
+            .find_node_of_value(&network)
+            .expect("Object has a node");
+        let min = node.get_area().min();
+        (min.x(), min.y())
+    }
+
+    #[test]
+    fn test_moved_entity_changes_node() {
+        let mut world = World::default();
+        let mut zones = test_zones(&[zone_config("1")], 2, 1);
+        let moving = spawn(&mut world, &mut zones, 1, 100.0, 100.0);
+        spawn(&mut world, &mut zones, 2, 600.0, 100.0);
+        spawn(&mut world, &mut zones, 3, 100.0, 600.0);
+        let mut resources = Resources::default();
+        resources.insert(zones);
+        let mut schedule = Schedule::builder().add_system(zone_sync_system()).build();
+
+        schedule.execute(&mut world, &mut resources);
+        assert_eq!(node_min(&resources, 1), (0.0, 0.0));
+
+        world
+            .entry_mut(moving)
+            .unwrap()
+            .get_component_mut::<Location>()
+            .unwrap()
+            .position = Position::from_coord(700.0, 800.0);
+        schedule.execute(&mut world, &mut resources);
+
+        assert_eq!(node_min(&resources, 1), (500.0, 500.0));
+        assert_eq!(node_min(&resources, 2), (500.0, 0.0));
+    }
+}
@@ -0,0 +1,290 @@
+use crate::game::components::movement::Location;
+use crate::game::components::navigation::Navigation;
+use crate::game::components::obj::GameObjectDescriptor;
+use crate::game::components::party::Party;
+use crate::game::location::pos::Positionable;
+use crate::game::map::instance::InstanceOwner;
+use crate::game::map::portal::Portal;
+use crate::game::resource::state_delta::StateDeltaCache;
+use crate::game::resource::zones::Zones;
+use crate::net::packet::state_delta::{
+    ObjectStateBatch, ObjectStateChange, ObjectStateDeltaPacket,
+};
+use legion::world::SubWorld;
+use legion::{maybe_changed, system, Entity, EntityStore, IntoQuery};
+
+/// Moves the objects, that walked into a portal, to the spawn point of its destination zone.
+/// Portals leading to an instanced zone move the object into the copy of its party.
+/// Clients see them leave and enter through the interest management. NPCs forget their
+/// destination, it belongs to the zone they left.
+#[system]
+#[read_component(Party)]
+#[write_component(Location)]
+#[write_component(Navigation)]
+#[write_component(GameObjectDescriptor)]
+pub fn zone_transfer(
+    world: &mut SubWorld,
+    #[resource] zones: &mut Zones,
+    #[resource] state_delta: &mut StateDeltaCache,
+) {
+    let transfers: Vec<(Entity, Portal, InstanceOwner)> =
+        <(Entity, &Location, &GameObjectDescriptor, Option<&Party>)>::query()
+            .filter(maybe_changed::<Location>())
+            .iter(world)
+            .filter_map(|(entity, location, obj, party)| {
+                let portal = zones.zones.get(&obj.zone_id)?.portal_at(location.position)?;
+                let owner = match party {
+                    Some(party) => InstanceOwner::Party(party.id),
+                    None => InstanceOwner::Player(*entity),
+                };
+                Some((*entity, portal.clone(), owner))
+            })
+            .collect();
+
+    for (entity, portal, owner) in transfers {
+        let mut entry = match world.entry_mut(entity) {
+            Ok(entry) => entry,
+            Err(_) => continue,
+        };
+        let destination = if zones.is_template(&portal.destination) {
+            zones.instance_of(&portal.destination, owner)
+        } else {
+            Some(portal.destination.clone())
+        };
+        let destination = match destination {
+            Some(destination) if zones.zones.contains_key(&destination) => destination,
+            _ => {
+                warn!("Portal leads to the undefined zone {}", &portal.destination);
+                continue;
+            }
+        };
+        let obj = match entry.get_component_mut::<GameObjectDescriptor>() {
+            Ok(obj) => obj,
+            Err(_) => continue,
+        };
+        let object = zones
+            .zones
+            .get_mut(&obj.zone_id)
+            .and_then(|zone| zone.grid.remove(&obj.id.network));
+        if let (Some(mut object), Some(zone)) = (object, zones.zones.get_mut(&destination)) {
+            object.set_position(portal.spawn);
+            zone.grid.add(obj.id.network, object);
+        }
+        info!("{} moved from zone {} to {}", obj, &obj.zone_id, &destination);
+        obj.zone_id = destination;
+        let id = obj.id.clone();
+
+        if let Ok(location) = entry.get_component_mut::<Location>() {
+            location.position = portal.spawn;
+        }
+        if let Ok(navigation) = entry.get_component_mut::<Navigation>() {
+            navigation.stop();
+        }
+        let mut batch = ObjectStateBatch::new();
+        batch.add(ObjectStateChange::Position(portal.spawn));
+        state_delta
+            .0
+            .push_back(ObjectStateDeltaPacket::new(id, batch));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::config::{PortalConfig, ZoneConfig};
+    use crate::game::components::movement::Location;
+    use crate::game::components::navigation::Navigation;
+    use crate::game::components::obj::GameObjectDescriptor;
+    use crate::game::components::party::Party;
+    use crate::game::location::pos::{Position, Positionable};
+    use crate::game::resource::network_id::NetworkIdRegistry;
+    use crate::game::resource::state_delta::StateDeltaCache;
+    use crate::game::resource::zones::test_util::{place, test_zones, zone_config};
+    use crate::game::resource::zones::Zones;
+    use crate::game::system::zone_transfer::zone_transfer_system;
+    use legion::{Entity, EntityStore, Resources, Schedule, World};
+
+    fn zone(id: &str, instanced: bool, portals: Vec<PortalConfig>) -> ZoneConfig {
+        ZoneConfig {
+            instanced,
+            portals,
+            ..zone_config(id)
+        }
+    }
+
+    fn portal(min: (f64, f64), destination: &str) -> PortalConfig {
+        PortalConfig {
+            min,
+            max: (min.0 + 100.0, min.1 + 100.0),
+            destination: destination.to_string(),
+            spawn: (50.0, 50.0),
+        }
+    }
+
+    fn zones() -> Zones {
+        test_zones(
+            &[
+                zone(
+                    "1",
+                    false,
+                    vec![portal((900.0, 0.0), "2"), portal((0.0, 900.0), "dungeon")],
+                ),
+                zone("2", false, Vec::new()),
+                zone("dungeon", true, Vec::new()),
+            ],
+            10,
+            2,
+        )
+    }
+
+    fn spawn(world: &mut World, zones: &mut Zones, network: u32) -> Entity {
+        let position = Position::from_coord(500.0, 500.0);
+        let entity = world.push((Location { position },));
+        place(world, zones, entity, network, position);
+        entity
+    }
+
+    fn move_to(world: &mut World, entity: Entity, x: f64, y: f64) {
+        world
+            .entry(entity)
+            .unwrap()
+            .get_component_mut::<Location>()
+            .unwrap()
+            .position = Position::from_coord(x, y);
+    }
+
+    fn zone_of(world: &World, entity: Entity) -> String {
+        world
+            .entry_ref(entity)
+            .unwrap()
+            .get_component::<GameObjectDescriptor>()
+            .unwrap()
+            .zone_id
+            .clone()
+    }
+
+    #[test]
+    fn test_walk_into_portal() {
+        let mut zones = zones();
+        let mut world = World::default();
+        let entity = spawn(&mut world, &mut zones, 1);
+        let mut resources = Resources::default();
+        resources.insert(zones);
+        resources.insert(StateDeltaCache::new());
+        let mut schedule = Schedule::builder()
+            .add_system(zone_transfer_system())
+            .build();
+
+        schedule.execute(&mut world, &mut resources);
+        assert!(resources.get::<StateDeltaCache>().unwrap().0.is_empty());
+
+        move_to(&mut world, entity, 950.0, 50.0);
+        schedule.execute(&mut world, &mut resources);
+
+        assert_eq!(zone_of(&world, entity), "2");
+        let entry = world.entry(entity).unwrap();
+        let location = entry.get_component::<Location>().unwrap().position;
+        assert_eq!((location.x(), location.y()), (50.0, 50.0));
+
+        let zones = resources.get::<Zones>().unwrap();
+        assert!(zones.zones["1"].grid.get(&1).is_none());
+        let arrived = zones.zones["2"].grid.get(&1).expect("Object is in zone 2");
+        assert_eq!(arrived.position().x(), 50.0);
+        assert_eq!(resources.get::<StateDeltaCache>().unwrap().0.len(), 1);
+    }
+
+    #[test]
+    fn test_portal_stops_navigation() {
+        let mut zones = zones();
+        let mut world = World::default();
+        let npc = spawn(&mut world, &mut zones, 1);
+        let mut navigation = Navigation::new();
+        navigation.go_to(Position::from_coord(950.0, 950.0));
+        navigation.path.push_back(Position::from_coord(700.0, 700.0));
+        world.entry(npc).unwrap().add_component(navigation);
+        let mut resources = Resources::default();
+        resources.insert(zones);
+        resources.insert(StateDeltaCache::new());
+        let mut schedule = Schedule::builder()
+            .add_system(zone_transfer_system())
+            .build();
+
+        move_to(&mut world, npc, 950.0, 50.0);
+        schedule.execute(&mut world, &mut resources);
+
+        assert_eq!(zone_of(&world, npc), "2");
+        let entry = world.entry(npc).unwrap();
+        let navigation = entry.get_component::<Navigation>().unwrap();
+        assert!(navigation.destination.is_none());
+        assert!(navigation.path.is_empty());
+    }
+
+    #[test]
+    fn test_party_shares_instance() {
+        let mut zones = zones();
+        let mut world = World::default();
+        let first = spawn(&mut world, &mut zones, 1);
+        let second = spawn(&mut world, &mut zones, 2);
+        let solo = spawn(&mut world, &mut zones, 3);
+        for member in &[first, second] {
+            world.entry(*member).unwrap().add_component(Party::new(1));
+        }
+        let mut resources = Resources::default();
+        resources.insert(zones);
+        resources.insert(StateDeltaCache::new());
+        let mut schedule = Schedule::builder()
+            .add_system(zone_transfer_system())
+            .build();
+
+        for entity in &[first, second, solo] {
+            move_to(&mut world, *entity, 50.0, 950.0);
+        }
+        schedule.execute(&mut world, &mut resources);
+
+        let party_zone = zone_of(&world, first);
+        let solo_zone = zone_of(&world, solo);
+        assert!(party_zone.starts_with("dungeon"));
+        assert_eq!(zone_of(&world, second), party_zone);
+        assert_ne!(solo_zone, party_zone);
+
+        let zones = resources.get::<Zones>().unwrap();
+        assert_eq!(zones.zones[&party_zone].grid.len(), 2);
+        assert_eq!(zones.zones[&solo_zone].grid.len(), 1);
+        assert!(zones.zones["1"].grid.is_empty());
+    }
+
+    #[test]
+    fn test_recycled_network_id_gets_own_instance() {
+        let mut zones = zones();
+        let mut world = World::default();
+        let mut network_ids = NetworkIdRegistry::new();
+        let network = network_ids.allocate();
+        let first = spawn(&mut world, &mut zones, network);
+        let mut resources = Resources::default();
+        resources.insert(zones);
+        resources.insert(StateDeltaCache::new());
+        let mut schedule = Schedule::builder()
+            .add_system(zone_transfer_system())
+            .build();
+
+        move_to(&mut world, first, 50.0, 950.0);
+        schedule.execute(&mut world, &mut resources);
+        let first_zone = zone_of(&world, first);
+        assert!(first_zone.starts_with("dungeon"));
+
+        // The first player logs out, the next one gets the same network ID
+        {
+            let mut zones = resources.get_mut::<Zones>().unwrap();
+            zones.zones.get_mut(&first_zone).unwrap().grid.remove(&network);
+            world.remove(first);
+            network_ids.release(network);
+            assert_eq!(network_ids.allocate(), network);
+        }
+        let second = spawn(&mut world, &mut resources.get_mut::<Zones>().unwrap(), network);
+        move_to(&mut world, second, 50.0, 950.0);
+        schedule.execute(&mut world, &mut resources);
+
+        let second_zone = zone_of(&world, second);
+        assert!(second_zone.starts_with("dungeon"));
+        assert_ne!(second_zone, first_zone);
+    }
+}
@@ -0,0 +1,92 @@
+use std::time::Duration;
+
+/// Accumulates the elapsed wall clock time and converts it to a number of fixed length ticks.
+///
+/// If the game loop falls behind, the missing ticks are simulated in a burst to catch up,
+/// but at most max_catch_up ticks at once, the rest of the lag is dropped.
+pub struct FixedTimestep {
+    step: Duration,
+    accumulator: Duration,
+    max_catch_up: u32,
+}
+
+impl FixedTimestep {
+    pub fn new(step: Duration, max_catch_up: u32) -> Self {
+        FixedTimestep {
+            step,
+            accumulator: Duration::new(0, 0),
+            max_catch_up,
+        }
+    }
+
+    /// Creates a timestep of 1 / tick_rate seconds with nanosecond precision.
+    pub fn from_tick_rate(tick_rate: u32, max_catch_up: u32) -> Self {
+        FixedTimestep::new(
+            Duration::from_nanos(1_000_000_000 / tick_rate.max(1) as u64),
+            max_catch_up,
+        )
+    }
+
+    pub fn step(&self) -> Duration {
+        self.step
+    }
+
+    /// Adds the elapsed time to the accumulator.
+    ///
+    /// # Returns
+    /// The number of ticks to be simulated.
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        self.accumulator += elapsed;
+        let mut ticks = 0;
+        while self.accumulator >= self.step {
+            if ticks == self.max_catch_up {
+                warn!(
+                    "Game loop is lagging behind, skipping {:?}",
+                    self.accumulator
+                );
+                self.accumulator = Duration::new(0, 0);
+                break;
+            }
+            self.accumulator -= self.step;
+            ticks += 1;
+        }
+        ticks
+    }
+
+    /// The time left until the next tick is due.
+    pub fn until_next_tick(&self) -> Duration {
+        self.step - self.accumulator
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::timestep::FixedTimestep;
+    use std::time::Duration;
+
+    #[test]
+    fn test_sub_second_step() {
+        let timestep = FixedTimestep::from_tick_rate(30, 5);
+        assert_eq!(timestep.step(), Duration::from_nanos(33_333_333));
+    }
+
+    #[test]
+    fn test_accumulate() {
+        let mut timestep = FixedTimestep::new(Duration::from_millis(50), 5);
+
+        assert_eq!(timestep.advance(Duration::from_millis(30)), 0);
+        assert_eq!(timestep.until_next_tick(), Duration::from_millis(20));
+        assert_eq!(timestep.advance(Duration::from_millis(30)), 1);
+        assert_eq!(timestep.until_next_tick(), Duration::from_millis(40));
+        assert_eq!(timestep.advance(Duration::from_millis(140)), 3);
+        assert_eq!(timestep.until_next_tick(), Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_catch_up_limit() {
+        let mut timestep = FixedTimestep::new(Duration::from_millis(50), 5);
+
+        assert_eq!(timestep.advance(Duration::from_secs(10)), 5);
+        assert_eq!(timestep.until_next_tick(), Duration::from_millis(50));
+    }
+}
@@ -6,3 +6,4 @@ pub mod error;
 pub mod user;
 pub mod game;
 pub mod common;
+pub mod config;
@@ -1,9 +1,15 @@
 use crate::error::error::{AuthError, Error};
 use crate::net::data::IntermediateGamePacket;
+use crate::net::packet::auth_challenge::AuthChallengePacket;
+use crate::net::packet::packet::S2CPacketBuilder;
+use crate::net::packet::reconnect_challenge::ReconnectChallengePacket;
 use crate::net::protocol::decode::ByteToRawDecoder;
-use crate::net::protocol::opcode::NetworkRecvOpCode;
+use crate::net::protocol::encode::BBEncodable;
+use crate::net::protocol::frame::encode_frame;
+use crate::net::protocol::opcode::{NetworkRecvOpCode, NetworkSendOpCode};
 use crate::net::provider::{DataStreamReader, DataStreamWriter};
-use crate::user::session::UserSessionManager;
+use crate::user::auth::{new_nonce, session_key, verify_session_proof, AuthPackage};
+use crate::user::session::{SharedSessionRegistry, UserSessionManager};
 use crate::user::user_event::UserChangeEvent;
 use bytes::{Bytes, BytesMut};
 use crossbeam_channel::unbounded;
@@ -12,14 +18,33 @@ use std::future::Future;
 use std::net::SocketAddr;
 use std::process::Output;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
 use tokio::net::TcpStream;
 use tokio::prelude::*;
+use tokio::sync::oneshot;
+use tokio::time::timeout;
 
 const BUFFER_LIMIT: usize = 1400;
+/// The whole authentication handshake must be completed within this time.
+const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
 
 type SafeManager<T> = Arc<Mutex<T>>;
 
+/// Writes a single packet wrapped in a frame to the socket.
+async fn write_packet(socket: &mut TcpStream, packet: &[u8]) -> Result<(), Error> {
+    let mut frame = BytesMut::new();
+    encode_frame(packet, &mut frame);
+    socket
+        .write_all(frame.as_ref())
+        .await
+        .map_err(|e| Error::new_network(&e.to_string()))?;
+    socket
+        .flush()
+        .await
+        .map_err(|e| Error::new_network(&e.to_string()))
+}
+
 /// Represents an open connection to a client. In order to receive additional messages from client,
 /// the first package must be a valid authentication package.
 pub struct DataStreamConnection {
@@ -28,6 +53,8 @@ pub struct DataStreamConnection {
     writer: Option<OwnedWriteHalf>,
     address: SocketAddr,
     authentication: Option<String>,
+    session_token: Option<Vec<u8>>,
+    decoder: ByteToRawDecoder,
 }
 
 impl DataStreamConnection {
@@ -38,6 +65,8 @@ impl DataStreamConnection {
             writer: None,
             address,
             authentication: None,
+            session_token: None,
+            decoder: ByteToRawDecoder::new(),
         }
     }
 
@@ -45,95 +74,262 @@ impl DataStreamConnection {
     pub fn is_authenticated(&self) -> bool {
         self.authentication.is_some()
     }
+
+    /// Token of the session issued by the authentication, to be sent to the client.
+    pub fn session_token(&self) -> Option<&[u8]> {
+        self.session_token.as_deref()
+    }
 }
 
 impl DataStreamConnection {
-    /// Waits for an authentication packet and validates it. Must be done before receiving
-    /// any other packet, otherwise, the session is dropped and closed.
+    /// Performs the authentication handshake. Must be done before receiving any other packet,
+    /// otherwise, the session is dropped and closed.
+    ///
+    /// The handshake consists of the following steps:
+    /// 1. The client sends AUTH with its username
+    /// 2. The server answers with AUTH_CHALLENGE, containing the hash parameters and a nonce
+    /// 3. The client sends AUTH_PROOF, which is the client key XOR HMAC-SHA256(stored key, nonce)
+    /// 4. The server sends the verdict as an AUTH packet
+    ///
+    /// Alternatively, the client may send RECONNECT with a session token issued by the server
+    /// in a previous connection. The server answers with RECONNECT_CHALLENGE containing a nonce,
+    /// and the client sends AUTH_PROOF, which is HMAC-SHA256(session key, nonce).
+    ///
+    /// A successful authentication registers the connection in the session registry, which
+    /// refuses the login, if the user is already connected and duplicate logins are rejected.
+    /// A reconnect is refused as long as the previous connection of the user is alive. The
+    /// session key is derived from the proven key and the nonce, and a new token is issued.
+    ///
+    /// The verdict is only sent by this function if the authentication fails, a successful
+    /// authentication must be acknowledged by the caller.
     pub async fn authenticate<T: UserSessionManager>(
         &mut self,
         session_manager: SafeManager<T>,
+        sessions: SharedSessionRegistry,
     ) -> Result<String, Error> {
-        if let Some(mut socket) = self.socket.take() {
+        let mut socket = self
+            .socket
+            .take()
+            .ok_or(Error::new_network("No session is stored"))?;
+
+        let result = match timeout(
+            AUTH_TIMEOUT,
+            self.handshake(&mut socket, session_manager, sessions),
+        )
+        .await
+        {
+            Ok(result) => result,
+            Err(_) => Err(AuthError::timeout()),
+        };
+
+        match result {
+            Ok(user) => {
+                debug!("Successfully authenticated {}", user);
+                self.authentication.replace(user.clone());
+                let (reader, writer) = socket.into_split();
+                self.writer = Some(writer);
+                self.reader = Some(reader);
+                Ok(user)
+            }
+            Err(e) => {
+                debug!("Authentication of {} failed: {}", self.address, e.to_string());
+                let verdict = match &e {
+                    Error::AuthError(auth_error) => AuthPackage::from(auth_error),
+                    _ => AuthPackage::AUTH_NETWORK_ERR,
+                };
+                let mut packet = BytesMut::new();
+                verdict.encode_as_bbp(&mut packet);
+                if let Err(write_error) = write_packet(&mut socket, &packet).await {
+                    debug!("Unable to send authentication verdict: {}", write_error);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    async fn handshake<T: UserSessionManager>(
+        &mut self,
+        socket: &mut TcpStream,
+        session_manager: SafeManager<T>,
+        sessions: SharedSessionRegistry,
+    ) -> Result<String, Error> {
+        let user = match self.read_packet(socket).await? {
+            IntermediateGamePacket::Auth { user } => user,
+            IntermediateGamePacket::Reconnect { token } => {
+                return self.reconnect(socket, sessions, &token).await;
+            }
+            packet => {
+                debug!("Packet is not AUTH {:?}", packet);
+                return Err(AuthError::unexpected_packet());
+            }
+        };
+
+        let nonce = new_nonce();
+        let (rounds, salt) = session_manager
+            .lock()
+            .map_err(|_| AuthError::invalid_user_or_password())?
+            .challenge_params(&user);
+        let challenge = AuthChallengePacket::new(rounds, salt, nonce.clone());
+        let mut packet = BytesMut::new();
+        S2CPacketBuilder::new()
+            .op_code(NetworkSendOpCode::AUTH_CHALLENGE)
+            .data(&challenge)
+            .build()?
+            .encode_as_bbp(&mut packet);
+        write_packet(socket, &packet).await?;
+
+        let proof = self.read_proof(socket).await?;
+        let key = session_manager
+            .lock()
+            .map_err(|_| AuthError::invalid_user_or_password())?
+            .verify_proof(&user, &nonce, &proof);
+        let key = match key {
+            Some(key) => key,
+            None => {
+                debug!("Unable to authenticate {} via user session manager", user);
+                return Err(AuthError::invalid_user_or_password());
+            }
+        };
+
+        let mut sessions = sessions
+            .lock()
+            .map_err(|_| AuthError::invalid_user_or_password())?;
+        let previous = sessions.connect(&user, self.address, false)?;
+        if let Some(previous) = previous {
+            info!("{} logged in again, dropping the connection of {}", user, previous);
+        }
+        self.session_token
+            .replace(sessions.issue(&user, session_key(&key, &nonce)));
+        Ok(user)
+    }
+
+    /// Challenges the client to prove that it holds the key of the session identified by the
+    /// token, before taking over the player of a lost connection.
+    async fn reconnect(
+        &mut self,
+        socket: &mut TcpStream,
+        sessions: SharedSessionRegistry,
+        token: &[u8],
+    ) -> Result<String, Error> {
+        let key = sessions
+            .lock()
+            .map_err(|_| AuthError::invalid_session())?
+            .key(token)
+            .map(|key| key.to_vec());
+        let key = match key {
+            Some(key) => key,
+            None => {
+                debug!("Unknown session token from {}", self.address);
+                return Err(AuthError::invalid_session());
+            }
+        };
+
+        let nonce = new_nonce();
+        let mut packet = BytesMut::new();
+        S2CPacketBuilder::new()
+            .op_code(NetworkSendOpCode::RECONNECT_CHALLENGE)
+            .data(&ReconnectChallengePacket::new(nonce.clone()))
+            .build()?
+            .encode_as_bbp(&mut packet);
+        write_packet(socket, &packet).await?;
+
+        let proof = self.read_proof(socket).await?;
+        if !verify_session_proof(&key, &nonce, &proof) {
+            debug!("Invalid session proof from {}", self.address);
+            return Err(AuthError::invalid_session());
+        }
+
+        let mut sessions = sessions
+            .lock()
+            .map_err(|_| AuthError::invalid_session())?;
+        let user = match sessions.resolve(token) {
+            Some(user) => user.to_string(),
+            None => return Err(AuthError::invalid_session()),
+        };
+        sessions.connect(&user, self.address, true)?;
+        self.session_token
+            .replace(sessions.issue(&user, session_key(&key, &nonce)));
+        Ok(user)
+    }
+
+    async fn read_proof(&mut self, socket: &mut TcpStream) -> Result<Vec<u8>, Error> {
+        match self.read_packet(socket).await? {
+            IntermediateGamePacket::AuthProof { proof } => Ok(proof),
+            packet => {
+                debug!("Packet is not AUTH_PROOF {:?}", packet);
+                Err(AuthError::unexpected_packet())
+            }
+        }
+    }
+
+    /// Reads the socket until a complete packet is received.
+    async fn read_packet(&mut self, socket: &mut TcpStream) -> Result<IntermediateGamePacket, Error> {
+        loop {
+            if let Some(packet) = self.decoder.next_packet()? {
+                return Ok(packet);
+            }
             let mut buf = [0 as u8; BUFFER_LIMIT];
-            let mut buf = BytesMut::from(buf.as_ref());
-            let first = socket
+            let n = socket
                 .read(buf.as_mut())
                 .await
                 .map_err(|e| Error::new_network(&e.to_string()))?;
-            debug!("Authentication bytes: #{} {:?}", first, buf);
-
-            let converter = ByteToRawDecoder::new();
-            let auth = converter.convert(&buf);
-            debug!("Converted {:?}", auth);
-
-            return match auth {
-                Ok(auth) => match auth {
-                    IntermediateGamePacket::Auth { user, hash } => {
-                        let manager = session_manager.lock();
-                        if !manager.is_ok() {
-                            return Err(AuthError::invalid_user_or_password());
-                        }
-                        if manager
-                            .unwrap()
-                            .is_auth_registered(user.as_str(), hash.as_str())
-                        {
-                            self.authentication.replace(hash.clone());
-                            debug!("Successfully authenticated {}", user);
-                            let (reader, writer) = socket.into_split();
-                            self.writer = Some(writer);
-                            self.reader = Some(reader);
-                            Ok((user.clone()))
-                        } else {
-                            debug!("Unable to authenticate via user session manager");
-                            Err(Error::new_network("Authentication failed"))
-                        }
-                    }
-                    _ => {
-                        debug!("Packet is not AUTH");
-                        Err(Error::NetworkError(
-                            "Authentication failed due to invalid packet type".to_string(),
-                        ))
-                    }
-                },
-                Err(e) => {
-                    debug!("{}", e.to_string());
-                    Err(Error::NetworkError(format!(
-                        "Authentication failed due to conversion error - {}",
-                        e.to_string()
-                    )))
-                }
-            };
-        } else {
-            return Err(Error::new_network("No session is stored"));
+            if n == 0 {
+                return Err(Error::new_network(
+                    "Connection closed before authentication",
+                ));
+            }
+            debug!("Authentication bytes: #{} {:?}", n, &buf[0..n]);
+            self.decoder.feed(&buf[0..n]);
         }
-
-        Err(AuthError::invalid_user_or_password())
     }
 
-    /// Spawns an asynchronous thread that is reading the output of the socket connection
+    /// Spawns an asynchronous thread that is reading the output of the socket connection.
+    /// The thread stops when the client closes the connection or the socket can not be read,
+    /// in which case the disconnection is reported via the user change channel.
     ///
     /// # Returns
     /// A stream, which wraps the receiving part of a channel in an asynchronous fashion.
-    pub async fn spawn_reader(&mut self) -> Result<DataStreamReader, Error> {
+    pub async fn spawn_reader(
+        &mut self,
+        user_change: Sender<UserChangeEvent>,
+    ) -> Result<DataStreamReader, Error> {
         let (tx, mut rx) = unbounded();
 
         if self.authentication.is_none() {
             return Err(Error::new_network("Client is not authenticated"));
         }
 
+        let addr = self.address.clone();
+
+        let (shutdown, mut shutdown_signal) = oneshot::channel::<()>();
+
         if let Some(mut socket) = self.reader.take() {
             tokio::spawn(async move {
                 loop {
                     let mut buf = [0 as u8; BUFFER_LIMIT];
-                    match socket.read(buf.as_mut()).await {
-                        Ok(n) if n == 0 => (),
-                        Ok(n) => {
-                            debug!("Received data {}", n);
-                            tx.send(Bytes::copy_from_slice(&buf));
-                        }
-                        Err(e) => {
-                            eprintln!("Error while reading socket");
+                    tokio::select! {
+                        read = socket.read(buf.as_mut()) => match read {
+                            Ok(0) => {
+                                info!("Connection closed by {}", addr);
+                                user_change.send(UserChangeEvent::DisconnectedUser(addr));
+                                break;
+                            }
+                            Ok(n) => {
+                                debug!("Received data {}", n);
+                                if tx.send(Bytes::copy_from_slice(&buf[0..n])).is_err() {
+                                    debug!("Reader of {} is dropped, stop reading socket", addr);
+                                    break;
+                                }
+                            }
+                            Err(e) => {
+                                error!("Error while reading socket of {}: {}", addr, e.to_string());
+                                user_change.send(UserChangeEvent::DisconnectedUser(addr));
+                                break;
+                            }
+                        },
+                        _ = &mut shutdown_signal => {
+                            debug!("Reader of {} is dropped, stop reading socket", addr);
+                            break;
                         }
                     }
                 }
@@ -144,7 +340,8 @@ impl DataStreamConnection {
             ));
         }
 
-        Ok(DataStreamReader::new(rx))
+        let decoder = std::mem::replace(&mut self.decoder, ByteToRawDecoder::new());
+        Ok(DataStreamReader::with_decoder(rx, decoder).with_shutdown(shutdown))
     }
 
     /// Spawns an asynchronous thread that is reading a channel and transmits the received
@@ -176,10 +373,12 @@ impl DataStreamConnection {
                             if let Err(e) = write_res {
                                 error!("Error writing data: {}", e.to_string());
                                 user_change.send(UserChangeEvent::DisconnectedUser(addr));
+                                break;
                             }
                         }
                         Err(e) => {
-                            // error!("Error receiving writable data: {}", e.to_string());
+                            debug!("Writer of {} is dropped, stop writing socket", addr);
+                            break;
                         }
                     };
                 }
@@ -196,17 +395,30 @@ impl DataStreamConnection {
 
 #[cfg(test)]
 mod tests {
+    use crate::config::config::DuplicateLoginPolicy;
     use crate::net::connection::DataStreamConnection;
+    use crate::net::protocol::cursor::ByteCursor;
     use crate::net::protocol::encode::{BBEncodable, ByteEncoder};
-    use crate::net::protocol::opcode::NetworkRecvOpCode;
-    use crate::user::session::UserSessionManager;
+    use crate::net::protocol::frame::{encode_frame, FrameDecoder};
+    use crate::net::protocol::opcode::{NetworkRecvOpCode, NetworkSendOpCode};
+    use crate::user::account::hash_password;
+    use crate::user::auth::{
+        client_key, compute_proof, compute_session_proof, stored_key, verify_proof, AuthPackage,
+    };
+    use crate::user::session::{SessionRegistry, UserSessionManager};
+    use crate::user::user_event::UserChangeEvent;
     use bytes::{Buf, Bytes, BytesMut};
     use env_logger::Env;
+    use std::convert::TryFrom;
     use std::sync::{Arc, Mutex};
+    use std::time::Duration;
     use tokio::net::TcpListener;
     use tokio::net::TcpStream;
     use tokio::prelude::*;
 
+    const SALT: &[u8] = b"salt";
+    const SESSION_KEY: &[u8] = b"session key";
+
     #[tokio::test]
     async fn test_bidirectional_messaging() {
         let env = Env::default().filter_or("BB_LOG_LEVEL", "debug");
@@ -222,18 +434,11 @@ mod tests {
             let mut tcp_stream = TcpStream::connect("localhost:44444")
                 .await
                 .expect("Can not connect to localhost");
-            let mut auth = BytesMut::new();
-            let mut encoder = ByteEncoder::new(&mut auth);
-            encoder.encode(&NetworkRecvOpCode::AUTH);
-            encoder.encode_str("test");
-            encoder.encode_str("test:test:test");
-            drop(encoder);
-            tcp_stream.write_all(auth.as_ref()).await;
-            tcp_stream.flush().await;
+            client_handshake(&mut tcp_stream, "test").await;
 
             let mut buf = [0 as u8; 1024];
             match tcp_stream.read(buf.as_mut()).await {
-                Ok(n) if n == 0 => {
+                Ok(0) => {
                     println!("0");
                 }
                 Ok(n) => {
@@ -254,19 +459,348 @@ mod tests {
         let (mut socket, addr) = client.accept().await.expect("Socket");
         let mut connection = DataStreamConnection::new(socket, addr);
         let authentication = connection
-            .authenticate(Arc::new(Mutex::new(FakeManager {})))
+            .authenticate(fake_manager(), SessionRegistry::shared(DuplicateLoginPolicy::KickOld))
             .await;
         authentication.expect("Authentication error");
-        let mut writer = connection.spawn_writer().await.expect("Writer error");
-        let mut reader = connection.spawn_reader().await.expect("Reader error");
+        let (user_change, _) = crossbeam_channel::unbounded();
+        let mut writer = connection
+            .spawn_writer(user_change.clone())
+            .await
+            .expect("Writer error");
+        let _reader = connection
+            .spawn_reader(user_change.clone())
+            .await
+            .expect("Reader error");
         writer.send(BytesMut::from(test_msg.as_bytes()).freeze());
     }
 
-    struct FakeManager {}
+    #[tokio::test]
+    async fn test_disconnect_on_close() {
+        let client = TcpListener::bind("localhost:44445")
+            .await
+            .expect("Can not start server on localhost");
+
+        tokio::spawn(async move {
+            let mut tcp_stream = TcpStream::connect("localhost:44445")
+                .await
+                .expect("Can not connect to localhost");
+            client_handshake(&mut tcp_stream, "test").await;
+        });
+
+        let (socket, addr) = client.accept().await.expect("Socket");
+        let mut connection = DataStreamConnection::new(socket, addr);
+        connection
+            .authenticate(fake_manager(), SessionRegistry::shared(DuplicateLoginPolicy::KickOld))
+            .await
+            .expect("Authentication error");
+        let (user_change, user_change_recv) = crossbeam_channel::unbounded();
+        let _reader = connection
+            .spawn_reader(user_change)
+            .await
+            .expect("Reader error");
+
+        let event = tokio::task::spawn_blocking(move || {
+            user_change_recv.recv_timeout(Duration::from_secs(5))
+        })
+        .await
+        .expect("Join error");
+        match event {
+            Ok(UserChangeEvent::DisconnectedUser(disconnected)) => assert_eq!(disconnected, addr),
+            _ => panic!("Disconnection was not reported"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalid_proof() {
+        let client = TcpListener::bind("localhost:44446")
+            .await
+            .expect("Can not start server on localhost");
+
+        let verdict = tokio::spawn(async move {
+            let mut tcp_stream = TcpStream::connect("localhost:44446")
+                .await
+                .expect("Can not connect to localhost");
+            let mut decoder = client_handshake(&mut tcp_stream, "wrong").await;
+            let frame = read_frame(&mut tcp_stream, &mut decoder).await;
+            let mut cursor = ByteCursor::new(&frame);
+            assert_eq!(cursor.as_u16(), Some(NetworkSendOpCode::AUTH as u16));
+            AuthPackage::try_from(cursor.as_u16().expect("No verdict")).expect("Invalid verdict")
+        });
+
+        let (socket, addr) = client.accept().await.expect("Socket");
+        let mut connection = DataStreamConnection::new(socket, addr);
+        assert!(connection
+            .authenticate(fake_manager(), SessionRegistry::shared(DuplicateLoginPolicy::KickOld))
+            .await
+            .is_err());
+        assert!(!connection.is_authenticated());
+        assert_eq!(
+            verdict.await.expect("Join error"),
+            AuthPackage::AUTH_INVALID_CRED
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_with_token() {
+        let client = TcpListener::bind("localhost:44447")
+            .await
+            .expect("Can not start server on localhost");
+        let sessions = SessionRegistry::shared(DuplicateLoginPolicy::KickOld);
+        let token = sessions.lock().unwrap().issue("test", SESSION_KEY.to_vec());
+
+        let client_token = token.clone();
+        tokio::spawn(async move {
+            let mut tcp_stream = TcpStream::connect("localhost:44447")
+                .await
+                .expect("Can not connect to localhost");
+            client_reconnect(&mut tcp_stream, &client_token, SESSION_KEY).await;
+        });
+
+        let (socket, addr) = client.accept().await.expect("Socket");
+        let mut connection = DataStreamConnection::new(socket, addr);
+        let user = connection
+            .authenticate(fake_manager(), sessions.clone())
+            .await
+            .expect("Authentication error");
+        assert_eq!(user, "test");
+        assert!(connection.is_authenticated());
+
+        let new_token = connection.session_token().expect("New token").to_vec();
+        let sessions = sessions.lock().unwrap();
+        assert_eq!(sessions.resolve(&token), None);
+        assert_eq!(sessions.resolve(&new_token), Some("test"));
+        assert_ne!(sessions.key(&new_token), Some(SESSION_KEY));
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_without_session_key() {
+        let client = TcpListener::bind("localhost:44450")
+            .await
+            .expect("Can not start server on localhost");
+        let sessions = SessionRegistry::shared(DuplicateLoginPolicy::KickOld);
+        let token = sessions.lock().unwrap().issue("test", SESSION_KEY.to_vec());
+
+        let client_token = token.clone();
+        let verdict = tokio::spawn(async move {
+            let mut tcp_stream = TcpStream::connect("localhost:44450")
+                .await
+                .expect("Can not connect to localhost");
+            let mut decoder = client_reconnect(&mut tcp_stream, &client_token, b"sniffed").await;
+            read_verdict(&mut tcp_stream, &mut decoder).await
+        });
+
+        let (socket, addr) = client.accept().await.expect("Socket");
+        let mut connection = DataStreamConnection::new(socket, addr);
+        assert!(connection
+            .authenticate(fake_manager(), sessions.clone())
+            .await
+            .is_err());
+        assert_eq!(
+            verdict.await.expect("Join error"),
+            AuthPackage::AUTH_INVALID_SESSION
+        );
+        assert_eq!(sessions.lock().unwrap().resolve(&token), Some("test"));
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_while_connected() {
+        let client = TcpListener::bind("localhost:44451")
+            .await
+            .expect("Can not start server on localhost");
+        let sessions = SessionRegistry::shared(DuplicateLoginPolicy::KickOld);
+        let token = sessions.lock().unwrap().issue("test", SESSION_KEY.to_vec());
+        sessions
+            .lock()
+            .unwrap()
+            .connect("test", "127.0.0.1:1000".parse().unwrap(), false)
+            .expect("Live connection");
+
+        let verdict = tokio::spawn(async move {
+            let mut tcp_stream = TcpStream::connect("localhost:44451")
+                .await
+                .expect("Can not connect to localhost");
+            let mut decoder = client_reconnect(&mut tcp_stream, &token, SESSION_KEY).await;
+            read_verdict(&mut tcp_stream, &mut decoder).await
+        });
+
+        let (socket, addr) = client.accept().await.expect("Socket");
+        let mut connection = DataStreamConnection::new(socket, addr);
+        assert!(connection
+            .authenticate(fake_manager(), sessions)
+            .await
+            .is_err());
+        assert_eq!(
+            verdict.await.expect("Join error"),
+            AuthPackage::AUTH_DUPLICATE_LOGIN
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_with_unknown_token() {
+        let client = TcpListener::bind("localhost:44448")
+            .await
+            .expect("Can not start server on localhost");
+
+        let verdict = tokio::spawn(async move {
+            let mut tcp_stream = TcpStream::connect("localhost:44448")
+                .await
+                .expect("Can not connect to localhost");
+            send_reconnect(&mut tcp_stream, b"unknown").await;
+            let frame = read_frame(&mut tcp_stream, &mut FrameDecoder::new()).await;
+            let mut cursor = ByteCursor::new(&frame);
+            assert_eq!(cursor.as_u16(), Some(NetworkSendOpCode::AUTH as u16));
+            AuthPackage::try_from(cursor.as_u16().expect("No verdict")).expect("Invalid verdict")
+        });
+
+        let (socket, addr) = client.accept().await.expect("Socket");
+        let mut connection = DataStreamConnection::new(socket, addr);
+        assert!(connection
+            .authenticate(fake_manager(), SessionRegistry::shared(DuplicateLoginPolicy::KickOld))
+            .await
+            .is_err());
+        assert_eq!(
+            verdict.await.expect("Join error"),
+            AuthPackage::AUTH_INVALID_SESSION
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reject_duplicate_login() {
+        let client = TcpListener::bind("localhost:44449")
+            .await
+            .expect("Can not start server on localhost");
+        let sessions = SessionRegistry::shared(DuplicateLoginPolicy::RejectNew);
+        sessions
+            .lock()
+            .unwrap()
+            .connect("test", "127.0.0.1:1000".parse().unwrap(), false)
+            .expect("First login");
+
+        let verdict = tokio::spawn(async move {
+            let mut tcp_stream = TcpStream::connect("localhost:44449")
+                .await
+                .expect("Can not connect to localhost");
+            let mut decoder = client_handshake(&mut tcp_stream, "test").await;
+            let frame = read_frame(&mut tcp_stream, &mut decoder).await;
+            let mut cursor = ByteCursor::new(&frame);
+            assert_eq!(cursor.as_u16(), Some(NetworkSendOpCode::AUTH as u16));
+            AuthPackage::try_from(cursor.as_u16().expect("No verdict")).expect("Invalid verdict")
+        });
+
+        let (socket, addr) = client.accept().await.expect("Socket");
+        let mut connection = DataStreamConnection::new(socket, addr);
+        assert!(connection
+            .authenticate(fake_manager(), sessions)
+            .await
+            .is_err());
+        assert_eq!(
+            verdict.await.expect("Join error"),
+            AuthPackage::AUTH_DUPLICATE_LOGIN
+        );
+    }
+
+    async fn send_reconnect(tcp_stream: &mut TcpStream, token: &[u8]) {
+        let mut reconnect = BytesMut::new();
+        let mut encoder = ByteEncoder::new(&mut reconnect);
+        encoder.encode(&NetworkRecvOpCode::RECONNECT);
+        encoder.encode_bytes(token);
+        send_packet(tcp_stream, &reconnect).await;
+    }
+
+    /// Performs the client side of a reconnection, proving the given session key.
+    async fn client_reconnect(tcp_stream: &mut TcpStream, token: &[u8], key: &[u8]) -> FrameDecoder {
+        send_reconnect(tcp_stream, token).await;
+
+        let mut decoder = FrameDecoder::new();
+        let challenge = read_frame(tcp_stream, &mut decoder).await;
+        let mut cursor = ByteCursor::new(&challenge);
+        assert_eq!(
+            cursor.as_u16(),
+            Some(NetworkSendOpCode::RECONNECT_CHALLENGE as u16)
+        );
+        let nonce = cursor.as_bytes().expect("No nonce");
+
+        let mut proof = BytesMut::new();
+        let mut encoder = ByteEncoder::new(&mut proof);
+        encoder.encode(&NetworkRecvOpCode::AUTH_PROOF);
+        encoder.encode_bytes(&compute_session_proof(key, &nonce));
+        send_packet(tcp_stream, &proof).await;
+        decoder
+    }
+
+    async fn read_verdict(tcp_stream: &mut TcpStream, decoder: &mut FrameDecoder) -> AuthPackage {
+        let frame = read_frame(tcp_stream, decoder).await;
+        let mut cursor = ByteCursor::new(&frame);
+        assert_eq!(cursor.as_u16(), Some(NetworkSendOpCode::AUTH as u16));
+        AuthPackage::try_from(cursor.as_u16().expect("No verdict")).expect("Invalid verdict")
+    }
+
+    async fn send_packet(tcp_stream: &mut TcpStream, packet: &BytesMut) {
+        let mut frame = BytesMut::new();
+        encode_frame(packet, &mut frame);
+        tcp_stream.write_all(frame.as_ref()).await.expect("Write error");
+        tcp_stream.flush().await.expect("Flush error");
+    }
+
+    async fn read_frame(tcp_stream: &mut TcpStream, decoder: &mut FrameDecoder) -> BytesMut {
+        loop {
+            if let Some(frame) = decoder.next_frame().expect("Invalid frame") {
+                return frame;
+            }
+            let mut buf = [0 as u8; 1024];
+            let n = tcp_stream.read(buf.as_mut()).await.expect("Read error");
+            assert_ne!(n, 0, "Connection closed");
+            decoder.extend(&buf[0..n]);
+        }
+    }
+
+    /// Performs the client side of the authentication handshake as user test.
+    async fn client_handshake(tcp_stream: &mut TcpStream, password: &str) -> FrameDecoder {
+        let mut auth = BytesMut::new();
+        let mut encoder = ByteEncoder::new(&mut auth);
+        encoder.encode(&NetworkRecvOpCode::AUTH);
+        encoder.encode_str("test");
+        send_packet(tcp_stream, &auth).await;
+
+        let mut decoder = FrameDecoder::new();
+        let challenge = read_frame(tcp_stream, &mut decoder).await;
+        let mut cursor = ByteCursor::new(&challenge);
+        assert_eq!(
+            cursor.as_u16(),
+            Some(NetworkSendOpCode::AUTH_CHALLENGE as u16)
+        );
+        let rounds = cursor.as_u32().expect("No rounds");
+        let salt = cursor.as_bytes().expect("No salt");
+        let nonce = cursor.as_bytes().expect("No nonce");
+
+        let mut proof = BytesMut::new();
+        let mut encoder = ByteEncoder::new(&mut proof);
+        encoder.encode(&NetworkRecvOpCode::AUTH_PROOF);
+        encoder.encode_bytes(&compute_proof(
+            &client_key(&hash_password(password, &salt, rounds)),
+            &nonce,
+        ));
+        send_packet(tcp_stream, &proof).await;
+        decoder
+    }
+
+    fn fake_manager() -> Arc<Mutex<FakeManager>> {
+        Arc::new(Mutex::new(FakeManager {
+            key: stored_key(&client_key(&hash_password("test", SALT, 1))),
+        }))
+    }
+
+    struct FakeManager {
+        key: Vec<u8>,
+    }
 
     impl UserSessionManager for FakeManager {
-        fn is_auth_registered(&self, user: &str, auth: &str) -> bool {
-            return true;
+        fn challenge_params(&self, _user: &str) -> (u32, Vec<u8>) {
+            (1, SALT.to_vec())
+        }
+
+        fn verify_proof(&self, _user: &str, nonce: &[u8], proof: &[u8]) -> Option<Vec<u8>> {
+            verify_proof(&self.key, nonce, proof)
         }
     }
 }
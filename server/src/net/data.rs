@@ -4,9 +4,13 @@ use crate::net::protocol::opcode::NetworkRecvOpCode;
 /// The intermediate representation of a BBP C2S message.
 #[derive(Debug)]
 pub enum IntermediateGamePacket {
-    Auth { user: String, hash: String },
+    Auth { user: String },
+    AuthProof { proof: Vec<u8> },
+    Reconnect { token: Vec<u8> },
     Flag { op_code: NetworkRecvOpCode },
-    PlayerInput {user: String, action: PlayerInputAction}
+    PlayerInput { action: PlayerInputAction },
+    Ping { seq: u32 },
+    Pong { seq: u32 },
 }
 
 impl Default for IntermediateGamePacket {
@@ -17,9 +21,31 @@ impl Default for IntermediateGamePacket {
     }
 }
 
+/// The action types of the MOVEMENT packet, represented as a single byte.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, TryFromPrimitive)]
 #[repr(u8)]
+pub enum PlayerInputCode {
+    MoveForward,
+    StopMove,
+    MoveBackward,
+    StrafeLeft,
+    StrafeRight,
+    TurnLeft,
+    TurnRight,
+    StopTurn,
+    SetFacing,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum PlayerInputAction {
     MoveForward,
-    StopMove
+    MoveBackward,
+    StrafeLeft,
+    StrafeRight,
+    StopMove,
+    TurnLeft,
+    TurnRight,
+    StopTurn,
+    /// Turns to an absolute angle in radians.
+    SetFacing(f32),
 }
@@ -0,0 +1,30 @@
+use crate::net::protocol::encode::{BBEncodable, ByteEncoder};
+use bytes::BytesMut;
+
+/// Second step of the authentication handshake. The client derives its key from the password
+/// with the given rounds and salt, and answers with the proof of the nonce in AUTH_PROOF.
+#[derive(Debug)]
+pub struct AuthChallengePacket {
+    rounds: u32,
+    salt: Vec<u8>,
+    nonce: Vec<u8>,
+}
+
+impl AuthChallengePacket {
+    pub fn new(rounds: u32, salt: Vec<u8>, nonce: Vec<u8>) -> Self {
+        AuthChallengePacket {
+            rounds,
+            salt,
+            nonce,
+        }
+    }
+}
+
+impl BBEncodable for AuthChallengePacket {
+    fn encode_as_bbp(&self, buf: &mut BytesMut) {
+        let mut encoder = ByteEncoder::new(buf);
+        encoder.encode_u32(self.rounds);
+        encoder.encode_bytes(&self.salt);
+        encoder.encode_bytes(&self.nonce);
+    }
+}
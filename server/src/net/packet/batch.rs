@@ -0,0 +1,74 @@
+use crate::net::protocol::encode::{BBEncodable, ByteEncoder};
+use bytes::{Bytes, BytesMut};
+
+/// Bytes of a batch frame besides its packets: frame length (u32), opcode (u16) and count (u32).
+pub const BATCH_HEADER_SIZE: usize = 10;
+/// Every packet in a batch is prefixed with its length (u32).
+const PACKET_HEADER_SIZE: usize = 4;
+
+/// Several packets sent in a single frame.
+pub struct BatchPacket<'a> {
+    packets: &'a [Bytes],
+}
+
+impl<'a> BatchPacket<'a> {
+    pub fn new(packets: &'a [Bytes]) -> Self {
+        BatchPacket { packets }
+    }
+}
+
+impl<'a> BBEncodable for BatchPacket<'a> {
+    fn encode_as_bbp(&self, buf: &mut BytesMut) {
+        let mut encoder = ByteEncoder::new(buf);
+        encoder.encode_u32(self.packets.len() as u32);
+        for packet in self.packets {
+            encoder.encode_bytes(packet);
+        }
+    }
+}
+
+/// Splits the packets into consecutive batches, whose encoded frame fits into the maximal size.
+/// Packets too big for any batch are put in one of their own.
+pub fn split_batches(packets: &[Bytes], max_size: usize) -> Vec<&[Bytes]> {
+    let mut batches = Vec::new();
+    let mut start = 0;
+    let mut size = BATCH_HEADER_SIZE;
+    for (i, packet) in packets.iter().enumerate() {
+        let packet_size = PACKET_HEADER_SIZE + packet.len();
+        if i > start && size + packet_size > max_size {
+            batches.push(&packets[start..i]);
+            start = i;
+            size = BATCH_HEADER_SIZE;
+        }
+        size += packet_size;
+    }
+    if start < packets.len() {
+        batches.push(&packets[start..]);
+    }
+    batches
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::net::packet::batch::{split_batches, BATCH_HEADER_SIZE};
+    use bytes::Bytes;
+
+    #[test]
+    fn test_split_batches() {
+        let packets: Vec<Bytes> = vec![
+            Bytes::from(vec![0; 10]),
+            Bytes::from(vec![1; 10]),
+            Bytes::from(vec![2; 10]),
+            Bytes::from(vec![3; 100]),
+            Bytes::from(vec![4; 5]),
+        ];
+
+        let batches = split_batches(&packets, BATCH_HEADER_SIZE + 28);
+        let lengths: Vec<usize> = batches.iter().map(|b| b.len()).collect();
+        assert_eq!(lengths, vec![2, 1, 1, 1]);
+        assert_eq!(batches[3][0], packets[4]);
+
+        assert_eq!(split_batches(&packets, 1000).len(), 1);
+        assert!(split_batches(&[], 1000).is_empty());
+    }
+}
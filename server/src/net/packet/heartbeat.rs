@@ -0,0 +1,22 @@
+use crate::net::protocol::encode::{BBEncodable, ByteEncoder};
+use bytes::BytesMut;
+
+/// Payload of the PING and PONG packets. The sequence number of a PONG must match the PING
+/// it answers.
+#[derive(Debug)]
+pub struct HeartbeatPacket {
+    seq: u32,
+}
+
+impl HeartbeatPacket {
+    pub fn new(seq: u32) -> Self {
+        HeartbeatPacket { seq }
+    }
+}
+
+impl BBEncodable for HeartbeatPacket {
+    fn encode_as_bbp(&self, buf: &mut BytesMut) {
+        let mut encoder = ByteEncoder::new(buf);
+        encoder.encode_u32(self.seq);
+    }
+}
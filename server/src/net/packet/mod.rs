@@ -1,3 +1,9 @@
 pub mod state_delta;
 pub mod spawn;
-pub mod packet;
\ No newline at end of file
+pub mod packet;
+pub mod heartbeat;
+pub mod auth_challenge;
+pub mod session_token;
+pub mod world_snapshot;
+pub mod batch;
+pub mod reconnect_challenge;
\ No newline at end of file
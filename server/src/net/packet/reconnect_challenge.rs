@@ -0,0 +1,22 @@
+use crate::net::protocol::encode::{BBEncodable, ByteEncoder};
+use bytes::BytesMut;
+
+/// Answer to RECONNECT. The client proves that it holds the key of the session with
+/// HMAC-SHA256(session key, nonce) in AUTH_PROOF.
+#[derive(Debug)]
+pub struct ReconnectChallengePacket {
+    nonce: Vec<u8>,
+}
+
+impl ReconnectChallengePacket {
+    pub fn new(nonce: Vec<u8>) -> Self {
+        ReconnectChallengePacket { nonce }
+    }
+}
+
+impl BBEncodable for ReconnectChallengePacket {
+    fn encode_as_bbp(&self, buf: &mut BytesMut) {
+        let mut encoder = ByteEncoder::new(buf);
+        encoder.encode_bytes(&self.nonce);
+    }
+}
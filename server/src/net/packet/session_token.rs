@@ -0,0 +1,23 @@
+use crate::net::protocol::encode::{BBEncodable, ByteEncoder};
+use bytes::BytesMut;
+
+/// Sent after a successful authentication. The token can be presented in a RECONNECT packet
+/// to take over the player entity after the connection is lost. It only identifies the session,
+/// the key of the session is derived by the client and never sent.
+#[derive(Debug)]
+pub struct SessionTokenPacket {
+    token: Vec<u8>,
+}
+
+impl SessionTokenPacket {
+    pub fn new(token: Vec<u8>) -> Self {
+        SessionTokenPacket { token }
+    }
+}
+
+impl BBEncodable for SessionTokenPacket {
+    fn encode_as_bbp(&self, buf: &mut BytesMut) {
+        let mut encoder = ByteEncoder::new(buf);
+        encoder.encode_bytes(&self.token);
+    }
+}
@@ -2,20 +2,24 @@ use crate::game::location::pos::Position;
 use crate::net::protocol::encode::{BBEncodable, ByteEncoder};
 use bytes::BytesMut;
 
+/// Introduces a game object to the client. The display name is only sent here, later state
+/// changes reference the object by its network ID.
 #[derive(Debug)]
 pub struct SpawnPacket {
+    name: String,
     location: Position
 }
 
 impl SpawnPacket {
-    pub fn new(location: Position) -> Self {
-        SpawnPacket { location }
+    pub fn new(name: String, location: Position) -> Self {
+        SpawnPacket { name, location }
     }
 }
 
 impl BBEncodable for SpawnPacket {
     fn encode_as_bbp(&self, buf: &mut BytesMut) {
        let mut encoder = ByteEncoder::new(buf);
+        encoder.encode_str(&self.name);
         encoder.encode(&self.location);
     }
 }
\ No newline at end of file
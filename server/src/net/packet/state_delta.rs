@@ -26,7 +26,7 @@ impl ObjectStateDeltaPacket {
 impl BBEncodable for ObjectStateDeltaPacket {
     fn encode_as_bbp(&self, buf: &mut BytesMut) {
         let mut encoder = ByteEncoder::new(buf);
-        encoder.encode_str(self.id.external.as_str());
+        encoder.encode_u32(self.id.network);
         encoder.encode(&self.delta_batch)
     }
 }
@@ -63,6 +63,10 @@ pub enum ObjectStateChange {
     Speed(f32),
     Spawn(SpawnPacket),
     DeSpawn,
+    /// Angle in radians
+    Facing(f32),
+    /// Position set by the server, after the object was refused to move where it intended
+    Correction(Position),
 }
 
 impl BBEncodable for ObjectStateChange {
@@ -83,6 +87,14 @@ impl BBEncodable for ObjectStateChange {
             ObjectStateChange::DeSpawn => {
                 buf.put_u8(3);
             }
+            ObjectStateChange::Facing(angle) => {
+                buf.put_u8(4);
+                buf.put_f32_le(*angle);
+            }
+            ObjectStateChange::Correction(p) => {
+                buf.put_u8(5);
+                p.encode_as_bbp(buf);
+            }
         }
     }
 }
@@ -0,0 +1,24 @@
+use crate::net::packet::state_delta::ObjectStateDeltaPacket;
+use crate::net::protocol::encode::{BBEncodable, ByteEncoder};
+use bytes::BytesMut;
+
+/// Full state of every object around a player, sent once after it enters the world.
+pub struct WorldSnapshotPacket {
+    objects: Vec<ObjectStateDeltaPacket>,
+}
+
+impl WorldSnapshotPacket {
+    pub fn new(objects: Vec<ObjectStateDeltaPacket>) -> Self {
+        WorldSnapshotPacket { objects }
+    }
+}
+
+impl BBEncodable for WorldSnapshotPacket {
+    fn encode_as_bbp(&self, buf: &mut BytesMut) {
+        let mut encoder = ByteEncoder::new(buf);
+        encoder.encode_u32(self.objects.len() as u32);
+        for object in &self.objects {
+            encoder.encode(object);
+        }
+    }
+}
@@ -17,6 +17,11 @@ impl<'a> ByteCursor<'a> {
         }
     }
 
+    /// Number of bytes processed so far.
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
     /// Converts a byte to an u8 value.
     ///
     /// # Returns
@@ -69,24 +74,46 @@ impl<'a> ByteCursor<'a> {
         }
     }
 
-    pub fn as_utf8(&mut self) -> Option<String> {
-        let len = self.as_u32();
-        match len {
-            Some(len) => {
-                let mut bytes = Vec::new();
-                for i in 0..len {
-                   if let Some(b) = self.buf.next() {
-                       bytes.push(*b);
-                   } else {
-                       return None
-                   }
-                }
-                return String::from_utf8(bytes).ok()
-            },
-            _ => None
+    /// Converts four bytes to an f32 value in a little endian fashion.
+    ///
+    /// # Returns
+    /// None if the conversion is unsuccessful due to missing bytes in the array.
+    pub fn as_f32(&mut self) -> Option<f32> {
+        self.as_u32().map(f32::from_bits)
+    }
+
+    /// Converts eight bytes to an f64 value in a little endian fashion.
+    ///
+    /// # Returns
+    /// None if the conversion is unsuccessful due to missing bytes in the array.
+    pub fn as_f64(&mut self) -> Option<f64> {
+        let low = self.as_u32()? as u64;
+        let high = self.as_u32()? as u64;
+        Some(f64::from_bits(high << 32 | low))
+    }
+
+    /// Converts a length (u32) prefixed byte array.
+    ///
+    /// # Returns
+    /// None if the conversion is unsuccessful due to missing bytes in the array.
+    pub fn as_bytes(&mut self) -> Option<Vec<u8>> {
+        let len = self.as_u32()?;
+        let mut bytes = Vec::new();
+        for _ in 0..len {
+            if let Some(b) = self.buf.next() {
+                bytes.push(*b);
+            } else {
+                return None;
+            }
         }
+        self.current += len as usize;
+        Some(bytes)
     }
 
+    pub fn as_utf8(&mut self) -> Option<String> {
+        self.as_bytes()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+    }
 }
 #[cfg(test)]
 mod tests {
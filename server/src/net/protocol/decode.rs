@@ -1,24 +1,54 @@
 use crate::error::error::Error;
-use crate::net::data::{IntermediateGamePacket, PlayerInputAction};
+use crate::net::data::{IntermediateGamePacket, PlayerInputAction, PlayerInputCode};
 use crate::net::protocol::cursor::ByteCursor;
+use crate::net::protocol::frame::FrameDecoder;
 use crate::net::protocol::opcode::NetworkRecvOpCode;
 use bytes::BytesMut;
 use std::convert::TryFrom;
 use std::slice::Iter;
 
 /// A byte to Rust raw unstructured type converter
-pub struct ByteToRawDecoder {}
+///
+/// The converter is stateful, it buffers the bytes of incomplete frames between calls.
+pub struct ByteToRawDecoder {
+    frames: FrameDecoder,
+}
 
 impl ByteToRawDecoder {
     pub fn new() -> Self {
-        ByteToRawDecoder {}
+        ByteToRawDecoder {
+            frames: FrameDecoder::new(),
+        }
+    }
+
+    /// Feeds raw stream bytes to the converter and converts the next complete frame to
+    /// unstructured Rust type
+    ///
+    /// # Returns
+    /// None if no complete frame is buffered yet.
+    pub fn convert(&mut self, buf: &[u8]) -> Result<Option<IntermediateGamePacket>, Error> {
+        self.feed(buf);
+        self.next_packet()
+    }
+
+    /// Buffers raw stream bytes without converting them.
+    pub fn feed(&mut self, buf: &[u8]) {
+        self.frames.extend(buf);
+    }
+
+    /// Converts the next complete frame, that is already buffered, without feeding new bytes.
+    pub fn next_packet(&mut self) -> Result<Option<IntermediateGamePacket>, Error> {
+        match self.frames.next_frame()? {
+            Some(frame) => self.convert_frame(&frame).map(Some),
+            None => Ok(None),
+        }
     }
 
-    /// Converts raw bytes to unstructured Rust type
+    /// Converts the bytes of a single frame to unstructured Rust type
     ///
     /// The bytes are interpreted in a little endian fashion as the following:
     /// 0-2 bytes: NetworkOpCode (u16)
-    pub fn convert(&self, buf: &BytesMut) -> Result<IntermediateGamePacket, Error> {
+    fn convert_frame(&self, buf: &BytesMut) -> Result<IntermediateGamePacket, Error> {
         let mut cursor = ByteCursor::new(buf);
 
         let op_code = match cursor.as_u16() {
@@ -45,7 +75,15 @@ impl ByteToRawDecoder {
     ) -> Result<IntermediateGamePacket, Error> {
         match op_code {
             NetworkRecvOpCode::AUTH => convert_auth(cursor),
+            NetworkRecvOpCode::AUTH_PROOF => convert_auth_proof(cursor),
+            NetworkRecvOpCode::RECONNECT => convert_reconnect(cursor),
             NetworkRecvOpCode::MOVEMENT => convert_movement(cursor),
+            NetworkRecvOpCode::PING => Ok(IntermediateGamePacket::Ping {
+                seq: convert_heartbeat_seq(cursor)?,
+            }),
+            NetworkRecvOpCode::PONG => Ok(IntermediateGamePacket::Pong {
+                seq: convert_heartbeat_seq(cursor)?,
+            }),
             NetworkRecvOpCode::UNKNOWN => Err(Error::new_network("Invalid OpCode")),
         }
     }
@@ -56,52 +94,87 @@ fn convert_auth(cursor: &mut ByteCursor) -> Result<IntermediateGamePacket, Error
     let user = cursor
         .as_utf8()
         .ok_or(Error::new_network("Invalid or missing username from AUTH"))?;
-    let hash = cursor
-        .as_utf8()
-        .ok_or(Error::new_network("Invalid or missing hash from AUTH"))?;
-    Ok(IntermediateGamePacket::Auth { user, hash })
+    Ok(IntermediateGamePacket::Auth { user })
+}
+
+#[inline]
+fn convert_auth_proof(cursor: &mut ByteCursor) -> Result<IntermediateGamePacket, Error> {
+    let proof = cursor
+        .as_bytes()
+        .ok_or(Error::new_network("Invalid or missing proof from AUTH_PROOF"))?;
+    Ok(IntermediateGamePacket::AuthProof { proof })
+}
+
+#[inline]
+fn convert_reconnect(cursor: &mut ByteCursor) -> Result<IntermediateGamePacket, Error> {
+    let token = cursor
+        .as_bytes()
+        .ok_or(Error::new_network("Invalid or missing token from RECONNECT"))?;
+    Ok(IntermediateGamePacket::Reconnect { token })
 }
 
 #[inline]
 fn convert_movement(cursor: &mut ByteCursor) -> Result<IntermediateGamePacket, Error> {
-    let user = cursor.as_utf8().ok_or(Error::new_network(
-        "Invalid or missing username from InputPacket",
-    ))?;
-    let action = cursor.as_u8().ok_or(Error::new_network(
+    let code = cursor.as_u8().ok_or(Error::new_network(
         "Invalid or missing action type from InputPacket",
     ))?;
-    Ok(IntermediateGamePacket::PlayerInput {
-        user,
-        action: PlayerInputAction::try_from(action)
-            .map_err(|e| Error::NetworkError(e.to_string()))?,
-    })
+    let action = match PlayerInputCode::try_from(code)
+        .map_err(|e| Error::NetworkError(e.to_string()))?
+    {
+        PlayerInputCode::MoveForward => PlayerInputAction::MoveForward,
+        PlayerInputCode::StopMove => PlayerInputAction::StopMove,
+        PlayerInputCode::MoveBackward => PlayerInputAction::MoveBackward,
+        PlayerInputCode::StrafeLeft => PlayerInputAction::StrafeLeft,
+        PlayerInputCode::StrafeRight => PlayerInputAction::StrafeRight,
+        PlayerInputCode::TurnLeft => PlayerInputAction::TurnLeft,
+        PlayerInputCode::TurnRight => PlayerInputAction::TurnRight,
+        PlayerInputCode::StopTurn => PlayerInputAction::StopTurn,
+        PlayerInputCode::SetFacing => {
+            let angle = cursor.as_f32().ok_or(Error::new_network(
+                "Invalid or missing angle from InputPacket",
+            ))?;
+            if !angle.is_finite() {
+                return Err(Error::new_network("Invalid angle in InputPacket"));
+            }
+            PlayerInputAction::SetFacing(angle)
+        }
+    };
+    Ok(IntermediateGamePacket::PlayerInput { action })
+}
+
+#[inline]
+fn convert_heartbeat_seq(cursor: &mut ByteCursor) -> Result<u32, Error> {
+    cursor.as_u32().ok_or(Error::new_network(
+        "Invalid or missing sequence number from PING/PONG",
+    ))
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::net::data::IntermediateGamePacket;
+    use crate::net::data::{IntermediateGamePacket, PlayerInputAction, PlayerInputCode};
     use crate::net::protocol::cursor::ByteCursor;
     use crate::net::protocol::decode::ByteToRawDecoder;
     use crate::net::protocol::encode::ByteEncoder;
+    use crate::net::protocol::frame::encode_frame;
     use crate::net::protocol::opcode::NetworkRecvOpCode;
     use bytes::{Buf, BytesMut};
 
     #[test]
     fn test_auth() {
-        let converter = ByteToRawDecoder::new();
+        let mut converter = ByteToRawDecoder::new();
         let mut bytes = BytesMut::new();
         let mut encoder = ByteEncoder::new(&mut bytes);
         encoder.encode(&NetworkRecvOpCode::AUTH);
         encoder.encode_str("test_user");
-        encoder.encode_str("hash12345");
+        let mut frame = BytesMut::new();
+        encode_frame(&bytes, &mut frame);
 
-        let raw = converter.convert(&bytes);
+        let raw = converter.convert(&frame);
 
-        if let Ok(raw) = raw {
+        if let Ok(Some(raw)) = raw {
             match raw {
-                IntermediateGamePacket::Auth { user, hash } => {
+                IntermediateGamePacket::Auth { user } => {
                     assert_eq!(user, "test_user");
-                    assert_eq!(hash, "hash12345");
                 }
                 _ => panic!("Invalid network opcode"),
             }
@@ -109,4 +182,71 @@ mod tests {
             panic!("Unsuccessful conversion")
         }
     }
+
+    #[test]
+    fn test_split_packets() {
+        let mut converter = ByteToRawDecoder::new();
+        let mut stream = BytesMut::new();
+        for user in &["first", "second"] {
+            let mut bytes = BytesMut::new();
+            let mut encoder = ByteEncoder::new(&mut bytes);
+            encoder.encode(&NetworkRecvOpCode::AUTH);
+            encoder.encode_str(user);
+            encode_frame(&bytes, &mut stream);
+        }
+
+        let split = stream.len() / 2 + 3;
+        let first = converter.convert(&stream[0..split]).expect("Conversion error");
+        let second = converter.convert(&stream[split..]).expect("Conversion error");
+        let third = converter.next_packet().expect("Conversion error");
+
+        match (first, second, third) {
+            (
+                Some(IntermediateGamePacket::Auth { user: a }),
+                Some(IntermediateGamePacket::Auth { user: b }),
+                None,
+            ) => {
+                assert_eq!(a, "first");
+                assert_eq!(b, "second");
+            }
+            _ => panic!("Frames were not split correctly"),
+        }
+    }
+
+    #[test]
+    fn test_movement() {
+        let mut converter = ByteToRawDecoder::new();
+        let mut bytes = BytesMut::new();
+        let mut encoder = ByteEncoder::new(&mut bytes);
+        encoder.encode(&NetworkRecvOpCode::MOVEMENT);
+        encoder.encode_u8(PlayerInputCode::StopMove as u8);
+        let mut frame = BytesMut::new();
+        encode_frame(&bytes, &mut frame);
+
+        match converter.convert(&frame) {
+            Ok(Some(IntermediateGamePacket::PlayerInput { action })) => {
+                assert_eq!(action, PlayerInputAction::StopMove)
+            }
+            _ => panic!("Unsuccessful conversion"),
+        }
+    }
+
+    #[test]
+    fn test_set_facing() {
+        let mut converter = ByteToRawDecoder::new();
+        let mut bytes = BytesMut::new();
+        let mut encoder = ByteEncoder::new(&mut bytes);
+        encoder.encode(&NetworkRecvOpCode::MOVEMENT);
+        encoder.encode_u8(PlayerInputCode::SetFacing as u8);
+        encoder.encode_f32(1.5);
+        let mut frame = BytesMut::new();
+        encode_frame(&bytes, &mut frame);
+
+        match converter.convert(&frame) {
+            Ok(Some(IntermediateGamePacket::PlayerInput { action })) => {
+                assert_eq!(action, PlayerInputAction::SetFacing(1.5))
+            }
+            _ => panic!("Unsuccessful conversion"),
+        }
+    }
 }
@@ -22,6 +22,12 @@ impl<'a> ByteEncoder<'a> {
         self.buf.extend(bytes);
     }
 
+    /// Encodes a byte array the same way as a string: length (u32) followed by the bytes.
+    pub fn encode_bytes(&mut self, b: &[u8]) {
+        self.buf.put_u32_le(b.len() as u32);
+        self.buf.extend_from_slice(b);
+    }
+
     pub fn encode_u16(&mut self, v: u16) {
         self.buf.put_u16_le(v);
     }
@@ -34,6 +40,10 @@ impl<'a> ByteEncoder<'a> {
         self.buf.put_u8(v);
     }
 
+    pub fn encode_f32(&mut self, v: f32) {
+        self.buf.put_f32_le(v);
+    }
+
     pub fn encode<T: BBEncodable>(&mut self, c: &T) {
         c.encode_as_bbp(self.buf);
     }
@@ -0,0 +1,124 @@
+use crate::error::error::Error;
+use bytes::{Buf, BufMut, BytesMut};
+
+/// Size of the length header that precedes every BBP packet.
+pub const FRAME_HEADER_SIZE: usize = 4;
+/// Upper bound of a single frame payload. Anything larger is treated as a corrupt stream.
+pub const MAX_FRAME_SIZE: usize = 65536;
+
+/// Wraps a BBP packet into a frame.
+///
+/// The frame is encoded as the following:
+/// 0-4 bytes: length of the packet in bytes (u32)
+/// 4-X bytes: the packet itself (opcode and protocol specific data)
+pub fn encode_frame(packet: &[u8], buf: &mut BytesMut) {
+    buf.reserve(FRAME_HEADER_SIZE + packet.len());
+    buf.put_u32_le(packet.len() as u32);
+    buf.extend_from_slice(packet);
+}
+
+/// A stateful splitter of a continuous byte stream into BBP frames.
+///
+/// Bytes can be fed in arbitrary chunks (e.g. as they arrive from a TCP socket), the decoder
+/// buffers incomplete frames until the rest of the bytes arrive.
+pub struct FrameDecoder {
+    buf: BytesMut,
+}
+
+impl FrameDecoder {
+    pub fn new() -> Self {
+        FrameDecoder {
+            buf: BytesMut::new(),
+        }
+    }
+
+    /// Appends raw bytes to the internal buffer.
+    pub fn extend(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Number of bytes buffered, that are not yet emitted as a frame.
+    pub fn buffered(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Takes the next complete frame out of the buffer.
+    ///
+    /// # Returns
+    /// The packet bytes without the length header, or None if the frame is not yet complete.
+    /// An error is returned if the length header exceeds MAX_FRAME_SIZE, in which case the
+    /// buffered bytes are discarded, as the stream can not be resynchronized.
+    pub fn next_frame(&mut self) -> Result<Option<BytesMut>, Error> {
+        if self.buf.len() < FRAME_HEADER_SIZE {
+            return Ok(None);
+        }
+
+        let mut header = [0 as u8; FRAME_HEADER_SIZE];
+        header.copy_from_slice(&self.buf[0..FRAME_HEADER_SIZE]);
+        let len = u32::from_le_bytes(header) as usize;
+        if len > MAX_FRAME_SIZE {
+            self.buf.clear();
+            return Err(Error::NetworkError(format!(
+                "Frame length {} exceeds the limit of {} bytes",
+                len, MAX_FRAME_SIZE
+            )));
+        }
+        if self.buf.len() < FRAME_HEADER_SIZE + len {
+            return Ok(None);
+        }
+
+        self.buf.advance(FRAME_HEADER_SIZE);
+        Ok(Some(self.buf.split_to(len)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::net::protocol::frame::{encode_frame, FrameDecoder, MAX_FRAME_SIZE};
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_split_frame() {
+        let mut bytes = BytesMut::new();
+        encode_frame(&[1, 2, 3, 4, 5], &mut bytes);
+
+        let mut decoder = FrameDecoder::new();
+        decoder.extend(&bytes[0..3]);
+        assert!(decoder.next_frame().unwrap().is_none());
+        decoder.extend(&bytes[3..6]);
+        assert!(decoder.next_frame().unwrap().is_none());
+        decoder.extend(&bytes[6..]);
+
+        let frame = decoder.next_frame().unwrap().expect("Frame is complete");
+        assert_eq!(frame.as_ref(), &[1, 2, 3, 4, 5]);
+        assert_eq!(decoder.buffered(), 0);
+    }
+
+    #[test]
+    fn test_coalesced_frames() {
+        let mut bytes = BytesMut::new();
+        encode_frame(&[1, 2], &mut bytes);
+        encode_frame(&[3], &mut bytes);
+        encode_frame(&[4, 5, 6], &mut bytes);
+
+        let mut decoder = FrameDecoder::new();
+        decoder.extend(&bytes[0..bytes.len() - 1]);
+
+        assert_eq!(decoder.next_frame().unwrap().unwrap().as_ref(), &[1, 2]);
+        assert_eq!(decoder.next_frame().unwrap().unwrap().as_ref(), &[3]);
+        assert!(decoder.next_frame().unwrap().is_none());
+
+        decoder.extend(&bytes[bytes.len() - 1..]);
+        assert_eq!(decoder.next_frame().unwrap().unwrap().as_ref(), &[4, 5, 6]);
+        assert!(decoder.next_frame().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_oversized_frame() {
+        let mut decoder = FrameDecoder::new();
+        decoder.extend(&((MAX_FRAME_SIZE + 1) as u32).to_le_bytes());
+
+        assert!(decoder.next_frame().is_err());
+        assert_eq!(decoder.buffered(), 0);
+    }
+}
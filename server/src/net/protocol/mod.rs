@@ -1,4 +1,5 @@
 pub mod decode;
 pub mod cursor;
 pub mod encode;
+pub mod frame;
 pub mod opcode;
\ No newline at end of file
@@ -9,7 +9,11 @@ use std::convert::TryFrom;
 pub enum NetworkRecvOpCode {
     UNKNOWN,
     AUTH,
-    MOVEMENT
+    MOVEMENT,
+    PING,
+    PONG,
+    AUTH_PROOF,
+    RECONNECT,
 }
 
 impl Default for NetworkRecvOpCode {
@@ -32,6 +36,13 @@ pub enum NetworkSendOpCode {
     UNKNOWN,
     AUTH,
     PLAYER_STATE_CHANGE,
+    PING,
+    PONG,
+    AUTH_CHALLENGE,
+    SESSION_TOKEN,
+    WORLD_SNAPSHOT,
+    BATCH,
+    RECONNECT_CHALLENGE,
 }
 
 impl BBEncodable for NetworkSendOpCode {
@@ -1,10 +1,14 @@
 use crate::error::error::Error;
 use crate::net::data::IntermediateGamePacket;
 use crate::net::protocol::decode::ByteToRawDecoder;
-use crate::net::protocol::encode::BBEncodable;
+use crate::net::packet::batch::{split_batches, BatchPacket};
+use crate::net::protocol::encode::ByteEncoder;
+use crate::net::protocol::frame::encode_frame;
+use crate::net::protocol::opcode::NetworkSendOpCode;
 use bytes::{Bytes, BytesMut};
 use crossbeam_channel::{Receiver, RecvError, SendError, Sender};
 use std::future::Future;
+use tokio::sync::oneshot;
 
 type Rec = Receiver<Bytes>;
 type Send = Sender<Bytes>;
@@ -13,49 +17,106 @@ type InternalMsg = Bytes;
 pub struct DataStreamReader {
     receiver: Rec,
     decoder: ByteToRawDecoder,
+    shutdown: Option<oneshot::Sender<()>>,
 }
 
 impl DataStreamReader {
     pub fn new(receiver: Rec) -> Self {
+        DataStreamReader::with_decoder(receiver, ByteToRawDecoder::new())
+    }
+
+    /// Creates a reader, that continues decoding from an already used decoder, so that bytes
+    /// buffered before the reader was created are not lost.
+    pub fn with_decoder(receiver: Rec, decoder: ByteToRawDecoder) -> Self {
         DataStreamReader {
             receiver,
-            decoder: ByteToRawDecoder::new(),
+            decoder,
+            shutdown: None,
         }
     }
+
+    /// Attaches a shutdown signal to the reader. The signal is fired when the reader is dropped,
+    /// so that the socket reading side can be stopped even if the client never sends anything.
+    pub fn with_shutdown(mut self, shutdown: oneshot::Sender<()>) -> Self {
+        self.shutdown.replace(shutdown);
+        self
+    }
 }
 
 impl DataStreamReader {
+    /// Blocks until a complete packet is received.
     pub fn recv(&mut self) -> Result<IntermediateGamePacket, Error> {
-        let bytes = BytesMut::from(
-            self.receiver
+        loop {
+            if let Some(packet) = self.decoder.next_packet()? {
+                return Ok(packet);
+            }
+            let bytes = self
+                .receiver
                 .recv()
-                .map_err(|e| Error::new_network(&e.to_string()))?.as_ref(),
-        );
-        self.decoder.convert(&bytes)
+                .map_err(|e| Error::new_network(&e.to_string()))?;
+            self.decoder.feed(&bytes);
+        }
     }
 
+    /// Returns the next complete packet if there is one, otherwise an error is returned
+    /// without blocking.
     pub fn try_recv(&mut self) -> Result<IntermediateGamePacket, Error> {
-        let bytes = BytesMut::from(
-            self.receiver
+        loop {
+            if let Some(packet) = self.decoder.next_packet()? {
+                return Ok(packet);
+            }
+            let bytes = self
+                .receiver
                 .try_recv()
-                .map_err(|e| Error::new_network(&e.to_string()))?.as_ref(),
-        );
-        self.decoder.convert(&bytes)
+                .map_err(|e| Error::new_network(&e.to_string()))?;
+            self.decoder.feed(&bytes);
+        }
     }
 }
 
 pub struct DataStreamWriter {
     sender: Send,
+    queue: Vec<Bytes>,
 }
 
 impl DataStreamWriter {
     pub fn new(sender: Send) -> Self {
-        DataStreamWriter { sender }
+        DataStreamWriter {
+            sender,
+            queue: Vec::new(),
+        }
     }
 }
 
 impl DataStreamWriter {
+    /// Sends a BBP packet to the client. The packet is wrapped in a frame before transmission.
     pub fn send(&mut self, data: Bytes) -> Result<(), SendError<Bytes>> {
-        self.sender.send(data)
+        let mut frame = BytesMut::new();
+        encode_frame(&data, &mut frame);
+        self.sender.send(frame.freeze())
+    }
+
+    /// Queues a BBP packet until the next flush.
+    pub fn queue(&mut self, data: Bytes) {
+        self.queue.push(data);
+    }
+
+    /// Sends the queued packets. Packets are packed into as few BATCH frames as the maximal
+    /// frame size allows.
+    pub fn flush(&mut self, max_batch_size: usize) -> Result<(), SendError<Bytes>> {
+        let queue = std::mem::take(&mut self.queue);
+        for batch in split_batches(&queue, max_batch_size) {
+            if batch.len() == 1 {
+                self.send(batch[0].clone())?;
+                continue;
+            }
+            let batch = BatchPacket::new(batch);
+            let mut buf = BytesMut::new();
+            let mut encoder = ByteEncoder::new(&mut buf);
+            encoder.encode(&NetworkSendOpCode::BATCH);
+            encoder.encode(&batch);
+            self.send(buf.freeze())?;
+        }
+        Ok(())
     }
 }
@@ -1,10 +1,15 @@
-use crate::error::error::AuthError;
+use crate::config::config::ServerConfig;
 use crate::error::error::Error;
 use crate::game::lobby::Lobby;
+use crate::game::resource::zones::Zones;
 use crate::net::connection::DataStreamConnection;
 use crate::net::protocol::encode::BBEncodable;
+use crate::net::packet::packet::S2CPacketBuilder;
+use crate::net::packet::session_token::SessionTokenPacket;
+use crate::net::protocol::opcode::NetworkSendOpCode;
 use crate::user::auth::AuthPackage;
-use crate::user::session::DefaultSessionManager;
+use crate::user::account::FileAccountStore;
+use crate::user::session::{SessionRegistry, SharedSessionRegistry};
 use crate::user::user::AuthenticatedUser;
 use bytes::{BufMut, BytesMut};
 use std::net::SocketAddr;
@@ -15,26 +20,38 @@ use crossbeam_channel::unbounded;
 use crate::user::user_event::UserChangeEvent;
 
 pub struct TcpServer {
-    address: SocketAddr,
+    config: ServerConfig,
 }
 
 impl TcpServer {
-    pub fn new(address: SocketAddr) -> Self {
-        TcpServer {
-            address,
-        }
+    pub fn new(config: ServerConfig) -> Self {
+        TcpServer { config }
     }
 
     pub async fn start(&mut self) -> Result<(), Error> {
-        let client = TcpListener::bind(&self.address).await.map_err(|e| {
+        let address = self.config.socket_addr()?;
+        let client = TcpListener::bind(&address).await.map_err(|e| {
             Error::new_network(&format!("Unable to start server {}", e.to_string()))
         })?;
-        let session_manager = Arc::new(Mutex::new(DefaultSessionManager::new()));
+        let session_manager = Arc::new(Mutex::new(FileAccountStore::open(
+            &self.config.accounts_file,
+        )?));
+        let zones = Zones::new(
+            &self.config.zones,
+            &self.config.quad_tree,
+            self.config.view_distance,
+            self.config.instances.teardown(),
+            &self.config.default_zone,
+        )?;
+        let sessions = SessionRegistry::shared(self.config.session.duplicate_login);
         let (user_change_send, user_change_recv) = unbounded();
+        let lobby_config = self.config.clone();
+        let lobby_sessions = sessions.clone();
+        info!("Started server on {}", address);
 
         tokio::spawn(async move {
-            let mut lobby = Lobby::new(user_change_recv);
-            lobby.start();
+            let mut lobby = Lobby::new(user_change_recv, lobby_sessions, lobby_config);
+            lobby.start(zones);
         });
 
         loop {
@@ -51,49 +68,71 @@ impl TcpServer {
             info!("Accepted connection from {}", addr);
 
             let manager = session_manager.clone();
+            let sessions = sessions.clone();
             tokio::spawn(async move {
                 let mut connection = DataStreamConnection::new(socket, addr.clone());
-                let auth_res = connection.authenticate(manager).await;
+                let user_name = match connection.authenticate(manager, sessions.clone()).await {
+                    Ok(user_name) => user_name,
+                    Err(e) => {
+                        warn!("Authentication failed for {}: {}", addr, e.to_string());
+                        return;
+                    }
+                };
+                info!("Authenticated {}", addr);
+
                 let writer = connection.spawn_writer(new_user_send.clone()).await;
-                let reader = connection.spawn_reader().await;
+                let reader = connection.spawn_reader(new_user_send.clone()).await;
                 debug!("Spawned writer");
                 if let Err(e) = writer {
                     error!("Unable to acquire writer: {}", e.to_string());
+                    release_session(&sessions, &user_name, addr);
                     return;
                 }
 
                 let mut writer = writer.expect("Unexpected error when unwrapping Writer");
                 let mut reader = reader.expect("Unexpected error when unwrapping Reader");
 
-                match auth_res {
-                    Err(e) => {
-                        error!("Authentication error: {}", e.to_string());
-                        let error = match e {
-                            Error::AuthError(auth_error) => AuthPackage::from(&auth_error),
-                            _ => AuthPackage::AUTH_NETWORK_ERR,
-                        };
-                        warn!("Authentication failed for {}", addr);
-                        let mut b = BytesMut::new();
-                        error.encode_as_bbp(&mut b);
-                        writer.send(b.freeze());
+                let mut b = BytesMut::new();
+                AuthPackage::AUTH_OK.encode_as_bbp(&mut b);
+                let res = writer.send(b.freeze());
+                if let Err(e) = res {
+                    error!("Error while sending authentication OK: {}", e.to_string());
+                    release_session(&sessions, &user_name, addr);
+                    return;
+                }
+                let token = match connection.session_token() {
+                    Some(token) => SessionTokenPacket::new(token.to_vec()),
+                    None => {
+                        error!("No session token is issued for {}", addr);
+                        release_session(&sessions, &user_name, addr);
                         return;
                     }
-                    Ok(user_name) => {
-                        info!("Authenticated {}", addr);
-                        let mut b = BytesMut::new();
-                        AuthPackage::AUTH_OK.encode_as_bbp(&mut b);
-                        let res = writer.send(b.freeze());
-                        if let Err(e) = res {
-                            error!("Error while sending authentication OK: {}", e.to_string());
-                            return;
-                        }
-                        let user =
-                            AuthenticatedUser::new(addr, user_name, Some(reader), Some(writer));
-                        debug!("Sent new user {} to lobby", &user);
-                        new_user_send.send(UserChangeEvent::NewUser(user));
+                };
+                if let Ok(packet) = S2CPacketBuilder::new()
+                    .op_code(NetworkSendOpCode::SESSION_TOKEN)
+                    .data(&token)
+                    .build()
+                {
+                    let mut b = BytesMut::new();
+                    packet.encode_as_bbp(&mut b);
+                    if let Err(e) = writer.send(b.freeze()) {
+                        error!("Error while sending session token: {}", e.to_string());
+                        release_session(&sessions, &user_name, addr);
+                        return;
                     }
                 }
+                let user = AuthenticatedUser::new(addr, user_name, Some(reader), Some(writer));
+                debug!("Sent new user {} to lobby", &user);
+                new_user_send.send(UserChangeEvent::NewUser(user));
             });
         }
     }
 }
+
+/// Removes the connection of a user from the registry, whose session could not be handed over
+/// to the lobby.
+fn release_session(sessions: &SharedSessionRegistry, user: &str, addr: SocketAddr) {
+    if let Ok(mut sessions) = sessions.lock() {
+        sessions.disconnect(user, addr);
+    }
+}
@@ -0,0 +1,313 @@
+use crate::error::error::Error;
+use crate::user::auth;
+use crate::user::session::UserSessionManager;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+pub const SALT_LENGTH: usize = 16;
+/// Number of SHA-256 iterations applied to the salted password.
+pub const HASH_ROUNDS: u32 = 10000;
+
+/// Derives the stored hash of a password as SHA-256(salt | password) iterated `rounds` times.
+pub fn hash_password(password: &str, salt: &[u8], rounds: u32) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(salt);
+    hasher.update(password.as_bytes());
+    let mut hash = hasher.finalize();
+    for _ in 1..rounds {
+        hash = Sha256::digest(&hash);
+    }
+    hash.to_vec()
+}
+
+fn derive_stored_key(password: &str, salt: &[u8], rounds: u32) -> Vec<u8> {
+    auth::stored_key(&auth::client_key(&hash_password(password, salt, rounds)))
+}
+
+/// Compares two byte arrays in constant time, regardless of where the first difference is.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b.iter()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[derive(Debug, Clone)]
+pub struct Account {
+    pub name: String,
+    pub rounds: u32,
+    pub salt: Vec<u8>,
+    /// SHA-256 of the client key derived from the password, see [`auth::stored_key`].
+    pub stored_key: Vec<u8>,
+}
+
+impl Account {
+    pub fn new(name: &str, password: &str) -> Self {
+        let mut salt = vec![0 as u8; SALT_LENGTH];
+        rand::thread_rng().fill_bytes(&mut salt);
+        let stored_key = derive_stored_key(password, &salt, HASH_ROUNDS);
+        Account {
+            name: name.to_string(),
+            rounds: HASH_ROUNDS,
+            salt,
+            stored_key,
+        }
+    }
+
+    pub fn verify(&self, password: &str) -> bool {
+        constant_time_eq(
+            &derive_stored_key(password, &self.salt, self.rounds),
+            &self.stored_key,
+        )
+    }
+
+    /// Parses a line of the account file in the format of name:rounds:salt:stored_key,
+    /// where salt and stored_key are hex encoded.
+    fn parse(line: &str) -> Option<Self> {
+        let mut parts = line.split(':');
+        let name = parts.next()?;
+        let rounds = parts.next()?.parse::<u32>().ok()?;
+        let salt = from_hex(parts.next()?)?;
+        let stored_key = from_hex(parts.next()?)?;
+        if name.is_empty() || parts.next().is_some() {
+            return None;
+        }
+        Some(Account {
+            name: name.to_string(),
+            rounds,
+            salt,
+            stored_key,
+        })
+    }
+
+    fn format(&self) -> String {
+        format!(
+            "{}:{}:{}:{}",
+            self.name,
+            self.rounds,
+            to_hex(&self.salt),
+            to_hex(&self.stored_key)
+        )
+    }
+}
+
+/// An account store persisted in a plain text file, one account per line.
+/// Passwords are never stored, only the stored key derived from their salted hashes. The client
+/// proves that it holds the client key, which can not be derived from the stored key, so a leaked
+/// account file does not allow logging in without the passwords.
+///
+/// Account files written before the stored key was introduced hold the salted hashes instead,
+/// their accounts must be recreated.
+pub struct FileAccountStore {
+    path: PathBuf,
+    accounts: HashMap<String, Account>,
+    secret: [u8; 32],
+}
+
+impl FileAccountStore {
+    /// Loads the accounts from the file. A missing file is treated as an empty store.
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        let mut accounts = HashMap::new();
+        match fs::read_to_string(path) {
+            Ok(content) => {
+                for (line_number, line) in content.lines().enumerate() {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let account = Account::parse(line.trim()).ok_or(Error::AccountError(
+                        format!("Malformed account in {} at line {}", path.display(), line_number + 1),
+                    ))?;
+                    accounts.insert(account.name.clone(), account);
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                warn!("Account file {} does not exist", path.display());
+            }
+            Err(e) => {
+                return Err(Error::AccountError(format!(
+                    "Unable to read {}: {}",
+                    path.display(),
+                    e
+                )))
+            }
+        }
+
+        let mut secret = [0 as u8; 32];
+        rand::thread_rng().fill_bytes(&mut secret);
+
+        Ok(FileAccountStore {
+            path: path.to_path_buf(),
+            accounts,
+            secret,
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Account> {
+        self.accounts.get(name)
+    }
+
+    pub fn account_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.accounts.keys().map(|n| n.as_str()).collect();
+        names.sort();
+        names
+    }
+
+    pub fn create_account(&mut self, name: &str, password: &str) -> Result<(), Error> {
+        validate_name(name)?;
+        if self.accounts.contains_key(name) {
+            return Err(Error::AccountError(format!("Account {} already exists", name)));
+        }
+        self.accounts
+            .insert(name.to_string(), Account::new(name, password));
+        self.save()
+    }
+
+    pub fn change_password(&mut self, name: &str, password: &str) -> Result<(), Error> {
+        if !self.accounts.contains_key(name) {
+            return Err(Error::AccountError(format!("Account {} does not exist", name)));
+        }
+        self.accounts
+            .insert(name.to_string(), Account::new(name, password));
+        self.save()
+    }
+
+    pub fn remove_account(&mut self, name: &str) -> Result<(), Error> {
+        if self.accounts.remove(name).is_none() {
+            return Err(Error::AccountError(format!("Account {} does not exist", name)));
+        }
+        self.save()
+    }
+
+    /// Writes the accounts to a temporary file, which replaces the original one.
+    fn save(&self) -> Result<(), Error> {
+        let mut content = String::new();
+        for name in self.account_names() {
+            content.push_str(&self.accounts[name].format());
+            content.push('\n');
+        }
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, content)
+            .and_then(|_| fs::rename(&tmp_path, &self.path))
+            .map_err(|e| {
+                Error::AccountError(format!("Unable to write {}: {}", self.path.display(), e))
+            })
+    }
+}
+
+impl UserSessionManager for FileAccountStore {
+    fn challenge_params(&self, user: &str) -> (u32, Vec<u8>) {
+        match self.accounts.get(user) {
+            Some(account) => (account.rounds, account.salt.clone()),
+            None => {
+                let mut hasher = Sha256::new();
+                hasher.update(&self.secret);
+                hasher.update(user.as_bytes());
+                (HASH_ROUNDS, hasher.finalize()[0..SALT_LENGTH].to_vec())
+            }
+        }
+    }
+
+    fn verify_proof(&self, user: &str, nonce: &[u8], proof: &[u8]) -> Option<Vec<u8>> {
+        auth::verify_proof(&self.accounts.get(user)?.stored_key, nonce, proof)
+    }
+}
+
+fn validate_name(name: &str) -> Result<(), Error> {
+    if name.is_empty() || name.contains(|c: char| c == ':' || c.is_whitespace()) {
+        return Err(Error::AccountError(format!("Invalid account name '{}'", name)));
+    }
+    Ok(())
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::user::account::{hash_password, FileAccountStore};
+    use crate::user::auth::{client_key, compute_proof, new_nonce};
+    use crate::user::session::UserSessionManager;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn login(store: &FileAccountStore, user: &str, password: &str) -> bool {
+        let (rounds, salt) = store.challenge_params(user);
+        let nonce = new_nonce();
+        let proof = compute_proof(&client_key(&hash_password(password, &salt, rounds)), &nonce);
+        store.verify_proof(user, &nonce, &proof).is_some()
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("bb_accounts_{}_{}", name, std::process::id()));
+        fs::remove_file(&path).ok();
+        path
+    }
+
+    #[test]
+    fn test_create_and_verify() {
+        let path = temp_path("verify");
+        let mut store = FileAccountStore::open(&path).expect("Empty store");
+        store.create_account("admin", "admin").expect("Account created");
+
+        assert!(store.get("admin").unwrap().verify("admin"));
+        assert!(!store.get("admin").unwrap().verify("wrong"));
+        assert!(login(&store, "admin", "admin"));
+        assert!(!login(&store, "admin", "wrong"));
+        assert!(!login(&store, "unknown", "admin"));
+        assert_eq!(
+            store.challenge_params("unknown"),
+            store.challenge_params("unknown")
+        );
+        assert!(store.create_account("admin", "other").is_err());
+        assert!(store.create_account("in:valid", "other").is_err());
+
+        let content = fs::read_to_string(&path).expect("Account file");
+        assert!(!content.contains(":admin"));
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_stored_key_does_not_log_in() {
+        let path = temp_path("leak");
+        let mut store = FileAccountStore::open(&path).expect("Empty store");
+        store.create_account("admin", "admin").expect("Account created");
+        let stored = store.get("admin").unwrap().stored_key.clone();
+
+        let nonce = new_nonce();
+        assert!(store
+            .verify_proof("admin", &nonce, &compute_proof(&stored, &nonce))
+            .is_none());
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_change_password_persisted() {
+        let path = temp_path("passwd");
+        let mut store = FileAccountStore::open(&path).expect("Empty store");
+        store.create_account("test", "first").expect("Account created");
+        store.create_account("other", "first").expect("Account created");
+        store.change_password("test", "second").expect("Password changed");
+        store.remove_account("other").expect("Account removed");
+
+        let reloaded = FileAccountStore::open(&path).expect("Stored accounts");
+        assert!(login(&reloaded, "test", "second"));
+        assert!(!login(&reloaded, "test", "first"));
+        assert_eq!(reloaded.account_names(), vec!["test"]);
+        fs::remove_file(&path).ok();
+    }
+}
@@ -1,22 +1,38 @@
 use crate::net::protocol::encode::BBEncodable;
 use bytes::{Bytes, BytesMut, BufMut};
+use hmac::{Hmac, Mac, NewMac};
 use num_enum::TryFromPrimitive;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
 use std::convert::TryFrom;
 use crate::error::error::AuthError;
+use crate::user::account::constant_time_eq;
 use crate::net::protocol::opcode::NetworkSendOpCode;
 
+pub const NONCE_LENGTH: usize = 32;
+
+type HmacSha256 = Hmac<Sha256>;
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq, TryFromPrimitive)]
 #[repr(u16)]
 pub enum AuthPackage {
     AUTH_OK,
     AUTH_INVALID_CRED,
-    AUTH_NETWORK_ERR
+    AUTH_NETWORK_ERR,
+    AUTH_UNEXPECTED_PACKET,
+    AUTH_TIMEOUT,
+    AUTH_INVALID_SESSION,
+    AUTH_DUPLICATE_LOGIN,
 }
 
 impl From<&AuthError> for AuthPackage {
     fn from(e: &AuthError) -> Self {
         match e {
-           AuthError::INVALID_USER => AuthPackage::AUTH_INVALID_CRED
+            AuthError::INVALID_USER => AuthPackage::AUTH_INVALID_CRED,
+            AuthError::UNEXPECTED_PACKET => AuthPackage::AUTH_UNEXPECTED_PACKET,
+            AuthError::TIMEOUT => AuthPackage::AUTH_TIMEOUT,
+            AuthError::INVALID_SESSION => AuthPackage::AUTH_INVALID_SESSION,
+            AuthError::DUPLICATE_LOGIN => AuthPackage::AUTH_DUPLICATE_LOGIN,
         }
     }
 }
@@ -26,4 +42,126 @@ impl BBEncodable for AuthPackage {
         NetworkSendOpCode::AUTH.encode_as_bbp(buf);
         buf.put_u16_le(*self as u16);
     }
-}
\ No newline at end of file
+}
+
+/// Generates a random, single use challenge for a handshake.
+pub fn new_nonce() -> Vec<u8> {
+    let mut nonce = vec![0 as u8; NONCE_LENGTH];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    nonce
+}
+
+const CLIENT_KEY_LABEL: &[u8] = b"Client Key";
+const SESSION_KEY_LABEL: &[u8] = b"Session Key";
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_varkey(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Derives the key the client proves it holds from the salted password hash, as
+/// HMAC-SHA256(salted password hash, "Client Key").
+pub fn client_key(salted_password: &[u8]) -> Vec<u8> {
+    hmac(salted_password, CLIENT_KEY_LABEL)
+}
+
+/// The only value stored by the server, SHA-256(client key). It is enough to check a proof, but
+/// not to compute one.
+pub fn stored_key(client_key: &[u8]) -> Vec<u8> {
+    Sha256::digest(client_key).to_vec()
+}
+
+/// Computes the proof of a handshake as client key XOR HMAC-SHA256(stored key, nonce).
+pub fn compute_proof(client_key: &[u8], nonce: &[u8]) -> Vec<u8> {
+    let signature = hmac(&stored_key(client_key), nonce);
+    client_key
+        .iter()
+        .zip(signature.iter())
+        .map(|(k, s)| k ^ s)
+        .collect()
+}
+
+/// Checks the proof of a handshake in constant time, by recovering the client key from it and
+/// comparing its hash with the stored key.
+///
+/// # Returns
+/// The client key, if the proof is valid.
+pub fn verify_proof(stored: &[u8], nonce: &[u8], proof: &[u8]) -> Option<Vec<u8>> {
+    let signature = hmac(stored, nonce);
+    if proof.len() != signature.len() {
+        return None;
+    }
+    let client_key: Vec<u8> = proof
+        .iter()
+        .zip(signature.iter())
+        .map(|(p, s)| p ^ s)
+        .collect();
+    if constant_time_eq(&stored_key(&client_key), stored) {
+        Some(client_key)
+    } else {
+        None
+    }
+}
+
+/// Derives the key of a session from the key proven in the handshake and its nonce, as
+/// HMAC-SHA256(key, "Session Key" | nonce). Both sides derive it, so it is never sent.
+pub fn session_key(key: &[u8], nonce: &[u8]) -> Vec<u8> {
+    hmac(key, &[SESSION_KEY_LABEL, nonce].concat())
+}
+
+/// Computes the proof of a reconnection as HMAC-SHA256(session key, nonce).
+pub fn compute_session_proof(session_key: &[u8], nonce: &[u8]) -> Vec<u8> {
+    hmac(session_key, nonce)
+}
+
+/// Checks the proof of a reconnection in constant time.
+pub fn verify_session_proof(session_key: &[u8], nonce: &[u8], proof: &[u8]) -> bool {
+    let mut mac = HmacSha256::new_varkey(session_key).expect("HMAC accepts keys of any length");
+    mac.update(nonce);
+    mac.verify(proof).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::user::account::hash_password;
+    use crate::user::auth::{
+        client_key, compute_proof, compute_session_proof, new_nonce, session_key, stored_key,
+        verify_proof, verify_session_proof,
+    };
+
+    #[test]
+    fn test_proof() {
+        let key = client_key(&hash_password("admin", b"salt", 10));
+        let stored = stored_key(&key);
+        let nonce = new_nonce();
+        let proof = compute_proof(&key, &nonce);
+
+        assert_eq!(verify_proof(&stored, &nonce, &proof), Some(key));
+        assert!(verify_proof(&stored, &new_nonce(), &proof).is_none());
+        let other = stored_key(&client_key(&hash_password("other", b"salt", 10)));
+        assert!(verify_proof(&other, &nonce, &proof).is_none());
+    }
+
+    #[test]
+    fn test_stored_key_is_not_a_valid_proof_key() {
+        let stored = stored_key(&client_key(&hash_password("admin", b"salt", 10)));
+        let nonce = new_nonce();
+
+        assert!(verify_proof(&stored, &nonce, &compute_proof(&stored, &nonce)).is_none());
+    }
+
+    #[test]
+    fn test_session_proof() {
+        let key = client_key(&hash_password("admin", b"salt", 10));
+        let login_nonce = new_nonce();
+        let session = session_key(&key, &login_nonce);
+        let nonce = new_nonce();
+        let proof = compute_session_proof(&session, &nonce);
+
+        assert_ne!(session, key);
+        assert!(verify_session_proof(&session, &nonce, &proof));
+        assert!(!verify_session_proof(&session, &new_nonce(), &proof));
+        assert!(!verify_session_proof(&session_key(&key, &new_nonce()), &nonce, &proof));
+    }
+}
@@ -0,0 +1,119 @@
+use std::time::{Duration, Instant};
+
+/// Tracks the liveness of a client connection and the round-trip latency measured by
+/// PING/PONG exchanges.
+#[derive(Debug)]
+pub struct Heartbeat {
+    last_received: Instant,
+    last_ping: Option<Instant>,
+    pending_ping: Option<(u32, Instant)>,
+    seq: u32,
+    latency: Option<Duration>,
+}
+
+impl Heartbeat {
+    pub fn new(now: Instant) -> Self {
+        Heartbeat {
+            last_received: now,
+            last_ping: None,
+            pending_ping: None,
+            seq: 0,
+            latency: None,
+        }
+    }
+
+    /// Registers that a packet was received from the client.
+    pub fn received(&mut self, now: Instant) {
+        self.last_received = now;
+    }
+
+    pub fn last_received(&self) -> Instant {
+        self.last_received
+    }
+
+    /// Checks if the client stayed silent for longer than the timeout.
+    pub fn is_timed_out(&self, now: Instant, timeout: Duration) -> bool {
+        now.saturating_duration_since(self.last_received) > timeout
+    }
+
+    /// Checks if a new PING is due, that is, no PING was sent in the last interval.
+    pub fn is_ping_due(&self, now: Instant, interval: Duration) -> bool {
+        match self.last_ping {
+            Some(last_ping) => now.saturating_duration_since(last_ping) >= interval,
+            None => true,
+        }
+    }
+
+    /// Registers a new PING, that replaces any unanswered one.
+    ///
+    /// # Returns
+    /// The sequence number to be sent in the PING packet.
+    pub fn ping(&mut self, now: Instant) -> u32 {
+        self.seq = self.seq.wrapping_add(1);
+        self.last_ping = Some(now);
+        self.pending_ping = Some((self.seq, now));
+        self.seq
+    }
+
+    /// Registers a PONG answer and updates the latency if it answers the pending PING.
+    ///
+    /// # Returns
+    /// The measured round-trip time, or None if the PONG is stale or unsolicited.
+    pub fn pong(&mut self, seq: u32, now: Instant) -> Option<Duration> {
+        match self.pending_ping {
+            Some((pending_seq, sent)) if pending_seq == seq => {
+                self.pending_ping = None;
+                let rtt = now.saturating_duration_since(sent);
+                self.latency = Some(rtt);
+                Some(rtt)
+            }
+            _ => None,
+        }
+    }
+
+    /// The last measured round-trip time.
+    pub fn latency(&self) -> Option<Duration> {
+        self.latency
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::user::heartbeat::Heartbeat;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_timeout() {
+        let start = Instant::now();
+        let mut heartbeat = Heartbeat::new(start);
+        let timeout = Duration::from_secs(10);
+
+        assert!(!heartbeat.is_timed_out(start + Duration::from_secs(5), timeout));
+        assert!(heartbeat.is_timed_out(start + Duration::from_secs(11), timeout));
+
+        heartbeat.received(start + Duration::from_secs(5));
+        assert!(!heartbeat.is_timed_out(start + Duration::from_secs(11), timeout));
+    }
+
+    #[test]
+    fn test_latency() {
+        let start = Instant::now();
+        let mut heartbeat = Heartbeat::new(start);
+        let interval = Duration::from_secs(5);
+
+        assert!(heartbeat.is_ping_due(start, interval));
+        let first = heartbeat.ping(start);
+        assert!(!heartbeat.is_ping_due(start + Duration::from_secs(1), interval));
+        assert!(heartbeat.pong(first + 1, start + Duration::from_millis(10)).is_none());
+        assert_eq!(
+            heartbeat.pong(first, start + Duration::from_millis(40)),
+            Some(Duration::from_millis(40))
+        );
+        assert_eq!(heartbeat.latency(), Some(Duration::from_millis(40)));
+        assert!(heartbeat.pong(first, start + Duration::from_millis(50)).is_none());
+
+        let second = heartbeat.ping(start + interval);
+        assert_ne!(first, second);
+        assert_eq!(heartbeat.latency(), Some(Duration::from_millis(40)));
+    }
+}
@@ -1,4 +1,6 @@
 pub mod session;
+pub mod account;
 pub mod auth;
+pub mod heartbeat;
 pub mod user;
 pub mod user_event;
\ No newline at end of file
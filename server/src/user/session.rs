@@ -1,34 +1,208 @@
-use sha2::Digest;
+use crate::config::config::DuplicateLoginPolicy;
+use crate::error::error::{AuthError, Error};
+use rand::RngCore;
 use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
-const TEMP_SECRET: &str = "secret12345";
+pub const TOKEN_LENGTH: usize = 32;
+
+pub type SharedSessionRegistry = Arc<Mutex<SessionRegistry>>;
 
 pub trait UserSessionManager {
-    fn is_auth_registered(&self, user: &str, auth: &str) -> bool;
+    /// Returns the hash parameters (rounds and salt) a client needs to derive its key for the
+    /// challenge of the given user. Unknown users must get plausible parameters as well, so
+    /// that the handshake does not reveal which accounts exist.
+    fn challenge_params(&self, user: &str) -> (u32, Vec<u8>);
+
+    /// Checks whether the proof is a valid answer to the nonce for the given user.
+    ///
+    /// # Returns
+    /// The key proven by the client, if the proof is valid.
+    fn verify_proof(&self, user: &str, nonce: &[u8], proof: &[u8]) -> Option<Vec<u8>>;
+}
+
+struct Session {
+    user: String,
+    /// Key derived by both sides during the handshake, the client proves it holds the key when
+    /// it reconnects. It is never sent, unlike the token.
+    key: Vec<u8>,
+    issued_at: Instant,
 }
 
-pub struct DefaultSessionManager {
-    temp_storage: HashMap<String, String>,
+/// Keeps track of the session tokens issued to authenticated users. A token identifies the
+/// session when reconnecting to the same player entity without going through the password
+/// handshake again, the client must also prove that it holds the key of the session.
+///
+/// Every user has at most one valid token, issuing a new one invalidates the previous.
+/// The registry also keeps track of the connected users and enforces the duplicate login policy.
+pub struct SessionRegistry {
+    sessions: HashMap<Vec<u8>, Session>,
+    tokens: HashMap<String, Vec<u8>>,
+    connections: HashMap<String, SocketAddr>,
+    duplicate_login: DuplicateLoginPolicy,
 }
 
-impl DefaultSessionManager {
-    pub fn new() -> Self {
-        let mut temp_storage = HashMap::new();
-        temp_storage.insert("admin".to_string(), format!("admin:admin:{}", TEMP_SECRET));
-        temp_storage.insert("test".to_string(), format!("test:test:{}", TEMP_SECRET));
-        DefaultSessionManager {
-            temp_storage,
+impl SessionRegistry {
+    pub fn new(duplicate_login: DuplicateLoginPolicy) -> Self {
+        SessionRegistry {
+            sessions: HashMap::new(),
+            tokens: HashMap::new(),
+            connections: HashMap::new(),
+            duplicate_login,
         }
     }
-}
 
-impl UserSessionManager for DefaultSessionManager {
-    fn is_auth_registered(&self, user: &str, auth: &str) -> bool {
-        if let Some(hash) = self.temp_storage.get(user) {
-            debug!("Stored hash {} ?= {}", hash, auth);
-            hash == auth
-        } else {
-            false
+    pub fn shared(duplicate_login: DuplicateLoginPolicy) -> SharedSessionRegistry {
+        Arc::new(Mutex::new(SessionRegistry::new(duplicate_login)))
+    }
+
+    /// Registers the connection of an authenticated user.
+    ///
+    /// If the user is already connected, a reconnect is always refused, the player can only be
+    /// taken over after its connection is lost. A new login is refused with the RejectNew policy.
+    ///
+    /// # Returns
+    /// The address of the replaced connection, if there was any.
+    pub fn connect(
+        &mut self,
+        user: &str,
+        addr: SocketAddr,
+        reconnect: bool,
+    ) -> Result<Option<SocketAddr>, Error> {
+        if self.connections.contains_key(user)
+            && (reconnect || self.duplicate_login == DuplicateLoginPolicy::RejectNew)
+        {
+            return Err(AuthError::duplicate_login());
         }
+        Ok(self.connections.insert(user.to_string(), addr))
+    }
+
+    /// Removes the connection of the user, if it is still the one from the given address.
+    pub fn disconnect(&mut self, user: &str, addr: SocketAddr) {
+        if self.connections.get(user) == Some(&addr) {
+            self.connections.remove(user);
+        }
+    }
+
+    /// Generates a new random token for the session of the user with the given key.
+    pub fn issue(&mut self, user: &str, key: Vec<u8>) -> Vec<u8> {
+        let mut token = vec![0 as u8; TOKEN_LENGTH];
+        rand::thread_rng().fill_bytes(&mut token);
+        if let Some(previous) = self.tokens.insert(user.to_string(), token.clone()) {
+            self.sessions.remove(&previous);
+        }
+        self.sessions.insert(
+            token.clone(),
+            Session {
+                user: user.to_string(),
+                key,
+                issued_at: Instant::now(),
+            },
+        );
+        token
+    }
+
+    /// Returns the user the token was issued to, if the token is still valid.
+    pub fn resolve(&self, token: &[u8]) -> Option<&str> {
+        self.sessions.get(token).map(|s| s.user.as_str())
+    }
+
+    /// Returns the key of the session, if the token is still valid.
+    pub fn key(&self, token: &[u8]) -> Option<&[u8]> {
+        self.sessions.get(token).map(|s| s.key.as_slice())
+    }
+
+    /// Invalidates the token of the user, if it was issued before the given instant.
+    /// Tokens issued later belong to a newer connection of the user and are kept.
+    pub fn revoke_before(&mut self, user: &str, instant: Instant) {
+        let issued_before = self
+            .tokens
+            .get(user)
+            .and_then(|token| self.sessions.get(token))
+            .map_or(false, |session| session.issued_at <= instant);
+        if issued_before {
+            if let Some(token) = self.tokens.remove(user) {
+                self.sessions.remove(&token);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::config::DuplicateLoginPolicy;
+    use crate::user::session::SessionRegistry;
+    use std::net::SocketAddr;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_reissue_invalidates_previous_token() {
+        let mut registry = SessionRegistry::new(DuplicateLoginPolicy::KickOld);
+        let first = registry.issue("admin", b"first".to_vec());
+        let second = registry.issue("admin", b"second".to_vec());
+
+        assert_ne!(first, second);
+        assert_eq!(registry.resolve(&first), None);
+        assert_eq!(registry.key(&first), None);
+        assert_eq!(registry.resolve(&second), Some("admin"));
+        assert_eq!(registry.key(&second), Some(&b"second"[..]));
+        assert_eq!(registry.resolve(b"unknown"), None);
+    }
+
+    #[test]
+    fn test_revoke_keeps_newer_token() {
+        let mut registry = SessionRegistry::new(DuplicateLoginPolicy::KickOld);
+        let before_issue = Instant::now() - Duration::from_secs(1);
+        let token = registry.issue("admin", b"key".to_vec());
+
+        registry.revoke_before("admin", before_issue);
+        assert_eq!(registry.resolve(&token), Some("admin"));
+
+        registry.revoke_before("admin", Instant::now());
+        assert_eq!(registry.resolve(&token), None);
+    }
+
+    #[test]
+    fn test_kick_old_connection() {
+        let mut registry = SessionRegistry::new(DuplicateLoginPolicy::KickOld);
+        let old: SocketAddr = "127.0.0.1:1000".parse().unwrap();
+        let new: SocketAddr = "127.0.0.1:1001".parse().unwrap();
+
+        assert_eq!(registry.connect("admin", old, false).unwrap(), None);
+        assert_eq!(registry.connect("admin", new, false).unwrap(), Some(old));
+
+        registry.disconnect("admin", old);
+        assert_eq!(registry.connect("admin", old, false).unwrap(), Some(new));
+    }
+
+    #[test]
+    fn test_reject_new_connection() {
+        let mut registry = SessionRegistry::new(DuplicateLoginPolicy::RejectNew);
+        let old: SocketAddr = "127.0.0.1:1000".parse().unwrap();
+        let new: SocketAddr = "127.0.0.1:1001".parse().unwrap();
+
+        assert!(registry.connect("admin", old, false).is_ok());
+        assert!(registry.connect("admin", new, false).is_err());
+        assert!(registry.connect("admin", new, true).is_err());
+
+        registry.disconnect("admin", new);
+        assert!(registry.connect("admin", old, false).is_err());
+        registry.disconnect("admin", old);
+        assert!(registry.connect("admin", old, false).is_ok());
+    }
+
+    #[test]
+    fn test_reconnect_only_when_link_dead() {
+        let mut registry = SessionRegistry::new(DuplicateLoginPolicy::KickOld);
+        let old: SocketAddr = "127.0.0.1:1000".parse().unwrap();
+        let new: SocketAddr = "127.0.0.1:1001".parse().unwrap();
+
+        assert!(registry.connect("admin", old, false).is_ok());
+        assert!(registry.connect("admin", new, true).is_err());
+
+        registry.disconnect("admin", old);
+        assert_eq!(registry.connect("admin", new, true).unwrap(), None);
     }
 }
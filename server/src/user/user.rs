@@ -1,12 +1,15 @@
 use crate::net::provider::{DataStreamReader, DataStreamWriter};
+use crate::user::heartbeat::Heartbeat;
 use std::net::SocketAddr;
 use std::fmt::{Display, Formatter};
+use std::time::Instant;
 
 pub struct AuthenticatedUser {
     pub addr: SocketAddr,
     pub name: String,
     pub reader: Option<DataStreamReader>,
     pub writer: Option<DataStreamWriter>,
+    pub heartbeat: Heartbeat,
 }
 
 impl AuthenticatedUser {
@@ -21,6 +24,7 @@ impl AuthenticatedUser {
             name,
             reader,
             writer,
+            heartbeat: Heartbeat::new(Instant::now()),
         }
     }
 }
@@ -1,17 +1,23 @@
 use bytes::BytesMut;
 use server::net::protocol::cursor::ByteCursor;
 use server::net::protocol::encode::{BBEncodable, ByteEncoder};
+use server::net::protocol::frame::{encode_frame, FrameDecoder};
 use server::net::protocol::opcode::{NetworkRecvOpCode, NetworkSendOpCode};
-use server::user::session::DefaultSessionManager;
+use server::user::account::{from_hex, hash_password, to_hex};
+use server::user::auth::{
+    client_key, compute_proof, compute_session_proof, session_key, AuthPackage,
+};
 use sha2::Digest;
 use std::convert::TryFrom;
 use std::io::{stdin, BufRead, BufReader};
 use std::net::SocketAddr;
 use std::str::FromStr;
+use std::sync::Arc;
 use tokio::fs;
 use tokio::io;
 use tokio::net::TcpStream;
 use tokio::prelude::*;
+use tokio::sync::Mutex;
 
 const LOCALHOST: &str = "127.0.0.1";
 const PORT: &str = "47331";
@@ -20,7 +26,13 @@ const PORT: &str = "47331";
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let addr = format!("{}:{}", LOCALHOST, PORT).parse::<SocketAddr>()?;
     let mut tcp_stream = TcpStream::connect(&addr).await?;
-    let (mut socket, mut stream) = tcp_stream.into_split();
+    let (mut socket, stream) = tcp_stream.into_split();
+    let stream = Arc::new(Mutex::new(stream));
+    let pong_stream = stream.clone();
+    let password = Arc::new(std::sync::Mutex::new(String::from("admin")));
+    let reader_password = password.clone();
+    let session = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let reader_session = session.clone();
 
     println!(
         r#"Commands are:
@@ -35,8 +47,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Shortcuts --------------
     login: Login with admin
     login test: Login with test
+    pass<password>: Password used to answer the next AUTH_CHALLENGE
+    reconnect<token>:<key>: Reconnect with the hex encoded session token and key of a previous connection
     move: Start moving
     stop: Stop moving
+    back: Start moving backward
+    left/right: Start strafing left/right
+    turnl/turnr: Start turning left/right
+    noturn: Stop turning
+    face<angle>: Face the angle (radians)
+    ping: Send a PING
+
+    PING, AUTH_CHALLENGE and RECONNECT_CHALLENGE packets of the server are answered automatically.
     "#
     );
     let mut buf = BytesMut::new();
@@ -45,27 +67,26 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     tokio::spawn(async move {
         println!("Spawned reader");
+        let mut decoder = FrameDecoder::new();
         loop {
             let mut buf = [0 as u8; 1400];
             match socket.read(buf.as_mut()).await {
-                Ok(n) if n == 0 => (),
-                Ok(_) => {
-                    let mut zero_counter = 0;
-                    let mut truncate_index = 0;
-                    for a in buf.iter() {
-                        if *a == 0 {
-                            zero_counter += 1;
-                        }
-                        if zero_counter == 10 {
-                            break;
-                        }
-                        truncate_index += 1;
+                Ok(n) if n == 0 => {
+                    println!("Connection closed by server");
+                    std::process::exit(0);
+                }
+                Ok(n) => {
+                    println!("Message received: {:?}", BytesMut::from(&buf[0..n]));
+                    for response in print_incoming_msg(
+                        &mut decoder,
+                        &buf[0..n],
+                        &reader_password,
+                        &reader_session,
+                    ) {
+                        let mut stream = pong_stream.lock().await;
+                        stream.write_all(response.as_ref()).await;
+                        stream.flush().await;
                     }
-                    println!(
-                        "Message received: {:?}",
-                        BytesMut::from(&buf[0..truncate_index])
-                    );
-                    print_incoming_msg(&buf);
                 }
                 Err(e) => {
                     eprintln!("Error while reading socket");
@@ -76,7 +97,45 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     for line in stdin().lock().lines() {
         let line = line?;
-        if shortcuts(&line, &mut encoder, &mut stored_lines) {
+        if line.starts_with("pass") {
+            *password.lock().unwrap() = line[4..].to_string();
+            println!("Password set");
+            continue;
+        }
+        if line.starts_with("face") {
+            match f32::from_str(&line[4..]) {
+                Ok(angle) => {
+                    encoder.encode_u16(2);
+                    encoder.encode_u8(8);
+                    encoder.encode_f32(angle);
+                    stored_lines.push("2".to_string());
+                    stored_lines.push("8".to_string());
+                    stored_lines.push(line[4..].to_string());
+                    println!("Stored lines {:?}", stored_lines);
+                }
+                Err(_) => println!("Invalid angle"),
+            }
+            continue;
+        }
+        if line.starts_with("reconnect") {
+            let mut parts = line[9..].splitn(2, ':');
+            match (
+                parts.next().and_then(from_hex),
+                parts.next().and_then(from_hex),
+            ) {
+                (Some(token), Some(key)) => {
+                    *session.lock().unwrap() = key;
+                    encoder.encode(&NetworkRecvOpCode::RECONNECT);
+                    encoder.encode_bytes(&token);
+                    stored_lines.push("6".to_string());
+                    stored_lines.push(line[9..].to_string());
+                    println!("Stored lines {:?}", stored_lines);
+                }
+                _ => println!("Invalid token or key"),
+            }
+            continue;
+        }
+        if shortcuts(&line, &mut encoder, &mut stored_lines, &password) {
             println!("Stored lines {:?}", stored_lines);
             continue;
         }
@@ -85,7 +144,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
         if line.contains("send") {
             println!("Message sent {:#?}", stored_lines);
-            stream.write_all(encoder.buf()).await?;
+            let mut frame = BytesMut::new();
+            encode_frame(encoder.buf(), &mut frame);
+            let mut stream = stream.lock().await;
+            stream.write_all(frame.as_ref()).await?;
             stream.flush().await?;
             stored_lines.clear();
             buf = BytesMut::new();
@@ -119,69 +181,249 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-fn shortcuts(line: &str, encoder: &mut ByteEncoder, stored: &mut Vec<String>) -> bool {
+fn shortcuts(
+    line: &str,
+    encoder: &mut ByteEncoder,
+    stored: &mut Vec<String>,
+    password: &std::sync::Mutex<String>,
+) -> bool {
     match line {
         "login" => {
             encoder.encode_u16(1);
             encoder.encode_str("admin");
-            encoder.encode_str("admin:admin:secret12345");
+            *password.lock().unwrap() = "admin".to_string();
             stored.push("1".to_string());
             stored.push("admin".to_string());
-            stored.push("admin:admin:secret12345".to_string());
             true
         }
         "login test" => {
             encoder.encode_u16(1);
             encoder.encode_str("test");
-            encoder.encode_str("test:test:secret12345");
+            *password.lock().unwrap() = "test".to_string();
             stored.push("1".to_string());
             stored.push("test".to_string());
-            stored.push("test:test:secret12345".to_string());
             true
         }
         "move" => {
             encoder.encode_u16(2);
-            encoder.encode_str("admin");
             encoder.encode_u8(0);
             stored.push("2".to_string());
-            stored.push("admin".to_string());
             stored.push("0".to_string());
             true
         }
         "stop" => {
             encoder.encode_u16(2);
-            encoder.encode_str("admin");
             encoder.encode_u8(1);
             stored.push("2".to_string());
-            stored.push("admin".to_string());
             stored.push("1".to_string());
             true
         }
+        "back" => {
+            encoder.encode_u16(2);
+            encoder.encode_u8(2);
+            stored.push("2".to_string());
+            stored.push("2".to_string());
+            true
+        }
+        "left" => {
+            encoder.encode_u16(2);
+            encoder.encode_u8(3);
+            stored.push("2".to_string());
+            stored.push("3".to_string());
+            true
+        }
+        "right" => {
+            encoder.encode_u16(2);
+            encoder.encode_u8(4);
+            stored.push("2".to_string());
+            stored.push("4".to_string());
+            true
+        }
+        "turnl" => {
+            encoder.encode_u16(2);
+            encoder.encode_u8(5);
+            stored.push("2".to_string());
+            stored.push("5".to_string());
+            true
+        }
+        "turnr" => {
+            encoder.encode_u16(2);
+            encoder.encode_u8(6);
+            stored.push("2".to_string());
+            stored.push("6".to_string());
+            true
+        }
+        "noturn" => {
+            encoder.encode_u16(2);
+            encoder.encode_u8(7);
+            stored.push("2".to_string());
+            stored.push("7".to_string());
+            true
+        }
+        "ping" => {
+            encoder.encode_u16(3);
+            encoder.encode_u32(0);
+            stored.push("3".to_string());
+            stored.push("0".to_string());
+            true
+        }
         _ => false,
     }
 }
 
-fn print_incoming_msg(msg: &[u8]) {
-    let mut bytes = BytesMut::from(msg.as_ref());
-    let mut cursor = ByteCursor::new(&bytes);
+/// Prints every complete frame of the incoming bytes.
+///
+/// # Returns
+/// The frames that must be sent back to the server as an answer (e.g. PONG).
+fn print_incoming_msg(
+    decoder: &mut FrameDecoder,
+    msg: &[u8],
+    password: &std::sync::Mutex<String>,
+    session: &std::sync::Mutex<Vec<u8>>,
+) -> Vec<BytesMut> {
+    let mut responses = Vec::new();
+    decoder.extend(msg);
+    loop {
+        match decoder.next_frame() {
+            Ok(Some(frame)) => {
+                for response in print_frame(&frame, password, session) {
+                    let mut response_frame = BytesMut::new();
+                    encode_frame(&response, &mut response_frame);
+                    responses.push(response_frame);
+                }
+            }
+            Ok(None) => return responses,
+            Err(e) => {
+                eprintln!("Invalid frame: {}", e);
+                return responses;
+            }
+        }
+    }
+}
+
+/// Prints the state changes of a single object.
+fn print_state_changes(cursor: &mut ByteCursor) {
+    let id = cursor.as_u32().expect("No object ID");
+    let len = cursor.as_u32().expect("No state changes length") as usize;
+    let end = cursor.current() + len;
+    while cursor.current() < end {
+        print!("Object {} -> ", id);
+        let sub_op = cursor.as_u8().expect("No STATE CHANGE sub OP");
+        match sub_op {
+            0 => println!(
+                "Position change {} {}",
+                cursor.as_f64().expect("No x"),
+                cursor.as_f64().expect("No y")
+            ),
+            1 => println!("Speed change {}", cursor.as_f32().expect("No speed")),
+            2 => println!(
+                "Spawn '{}' at {} {}",
+                cursor.as_utf8().expect("No spawn name"),
+                cursor.as_f64().expect("No x"),
+                cursor.as_f64().expect("No y")
+            ),
+            3 => println!("Disconnected"),
+            4 => println!("Facing {}", cursor.as_f32().expect("No facing")),
+            5 => println!(
+                "Position corrected to {} {}",
+                cursor.as_f64().expect("No x"),
+                cursor.as_f64().expect("No y")
+            ),
+            _ => {
+                println!("Unknown state change {}", sub_op);
+                return;
+            }
+        };
+    }
+}
+
+/// Prints a single packet.
+///
+/// # Returns
+/// The packets that must be sent back to the server as an answer.
+fn print_frame(
+    bytes: &BytesMut,
+    password: &std::sync::Mutex<String>,
+    session: &std::sync::Mutex<Vec<u8>>,
+) -> Vec<BytesMut> {
+    let mut cursor = ByteCursor::new(bytes);
     let op = cursor.as_u16().expect("No OP");
     println!("{}", op);
     let op = NetworkSendOpCode::try_from(op).expect("Convert OP error");
     println!("{:#?}", op);
     match op {
-        NetworkSendOpCode::PLAYER_STATE_CHANGE => {
-            let player = cursor.as_utf8().expect("No player");
-            print!("Player '{}' -> ", player);
-            cursor.as_u32();
-            let sub_op = cursor.as_u8().expect("No STATE CHANGE sub OP");
-            match sub_op {
-                0 => println!("Position change"),
-                1 => println!("Speed change"),
-                2 => println!("Spawn"),
-                3 => println!("Disconnected"),
-                _ => (),
-            };
+        NetworkSendOpCode::PLAYER_STATE_CHANGE => print_state_changes(&mut cursor),
+        NetworkSendOpCode::WORLD_SNAPSHOT => {
+            let count = cursor.as_u32().expect("No object count");
+            println!("World snapshot of {} objects", count);
+            for _ in 0..count {
+                print_state_changes(&mut cursor);
+            }
+        }
+        NetworkSendOpCode::PING => {
+            let seq = cursor.as_u32().expect("No PING sequence");
+            println!("Answering PING {}", seq);
+            let mut pong = BytesMut::new();
+            let mut encoder = ByteEncoder::new(&mut pong);
+            encoder.encode(&NetworkRecvOpCode::PONG);
+            encoder.encode_u32(seq);
+            return vec![pong];
+        }
+        NetworkSendOpCode::AUTH => {
+            let code = cursor.as_u16().expect("No AUTH verdict");
+            match AuthPackage::try_from(code) {
+                Ok(verdict) => println!("{:?}", verdict),
+                Err(_) => println!("Unknown AUTH verdict {}", code),
+            }
+        }
+        NetworkSendOpCode::AUTH_CHALLENGE => {
+            let rounds = cursor.as_u32().expect("No rounds");
+            let salt = cursor.as_bytes().expect("No salt");
+            let nonce = cursor.as_bytes().expect("No nonce");
+            println!("Answering AUTH_CHALLENGE with {} rounds", rounds);
+            let key = client_key(&hash_password(&password.lock().unwrap(), &salt, rounds));
+            *session.lock().unwrap() = session_key(&key, &nonce);
+            let mut proof = BytesMut::new();
+            let mut encoder = ByteEncoder::new(&mut proof);
+            encoder.encode(&NetworkRecvOpCode::AUTH_PROOF);
+            encoder.encode_bytes(&compute_proof(&key, &nonce));
+            return vec![proof];
+        }
+        NetworkSendOpCode::RECONNECT_CHALLENGE => {
+            let nonce = cursor.as_bytes().expect("No nonce");
+            println!("Answering RECONNECT_CHALLENGE");
+            let mut session = session.lock().unwrap();
+            let mut proof = BytesMut::new();
+            let mut encoder = ByteEncoder::new(&mut proof);
+            encoder.encode(&NetworkRecvOpCode::AUTH_PROOF);
+            encoder.encode_bytes(&compute_session_proof(&session, &nonce));
+            *session = session_key(&session, &nonce);
+            return vec![proof];
+        }
+        NetworkSendOpCode::SESSION_TOKEN => {
+            let token = cursor.as_bytes().expect("No session token");
+            println!(
+                "Session token {}:{}",
+                to_hex(&token),
+                to_hex(&session.lock().unwrap())
+            );
+        }
+        NetworkSendOpCode::PONG => {
+            let seq = cursor.as_u32().expect("No PONG sequence");
+            println!("PONG {}", seq);
+        }
+        NetworkSendOpCode::BATCH => {
+            let count = cursor.as_u32().expect("No packet count");
+            println!("Batch of {} packets", count);
+            let mut responses = Vec::new();
+            for _ in 0..count {
+                let packet = cursor.as_bytes().expect("No batched packet");
+                responses.extend(print_frame(&BytesMut::from(&packet[..]), password, session));
+            }
+            return responses;
         }
         _ => (),
     };
+
+    Vec::new()
 }